@@ -0,0 +1,239 @@
+use std::rc::Rc;
+
+use crate::domain::Span;
+
+/*
+    A Chunk is a sequence of bytecode instructions for the virtual machine,
+    together with the constants they refer to.
+
+    Each instruction is a one byte opcode followed by its operands. Constant,
+    global and property names are u16 indexes into the constant pool, local
+    slots, upvalue indexes and argument counts are single bytes, and jumps are
+    u16 offsets.
+
+    The source line of every byte is kept in a run-length encoded table, so a
+    run of instructions from one line costs a single entry. The span of each
+    instruction is kept the same way, for error messages.
+
+    Reference - https://craftinginterpreters.com/chunks-of-bytecode.html
+*/
+
+macro_rules! opcodes {
+    ($($name:ident),* $(,)?) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #[repr(u8)]
+        pub enum OpCode {
+            $($name),*
+        }
+
+        impl OpCode {
+            pub const ALL: &'static [OpCode] = &[$(OpCode::$name),*];
+        }
+    };
+}
+
+opcodes! {
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    GetLocal,
+    SetLocal,
+    GetGlobal,
+    DefineGlobal,
+    SetGlobal,
+    GetUpvalue,
+    SetUpvalue,
+    GetProperty,
+    SetProperty,
+    GetSuper,
+    Equal,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    Print,
+    Jump,
+    JumpIfFalse,
+    Loop,
+    Call,
+    Closure,
+    CloseUpvalue,
+    Return,
+    Class,
+    Inherit,
+    Method,
+    // new opcodes go last, so compiled files keep their meaning
+    Stringify,
+}
+
+// `GetLocal` is shown as `OP_GET_LOCAL`
+impl std::fmt::Display for OpCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut name = String::from("OP");
+        for c in format!("{:?}", self).chars() {
+            if c.is_ascii_uppercase() {
+                name.push('_');
+            }
+            name.push(c.to_ascii_uppercase());
+        }
+        f.pad(&name)
+    }
+}
+
+impl TryFrom<u8> for OpCode {
+    type Error = u8;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        OpCode::ALL.get(byte as usize).copied().ok_or(byte)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Number(f64),
+    String(String),
+    Function(Rc<Function>),
+}
+
+impl std::fmt::Display for Constant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Constant::Number(number) => write!(f, "{:?}", number),
+            Constant::String(string) => write!(f, "{}", string),
+            Constant::Function(function) => write!(f, "{}", function),
+        }
+    }
+}
+
+// a compiled function, the top level script is one without a name
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Function {
+    pub name: String,
+    pub arity: usize,
+    pub upvalue_count: usize,
+    pub chunk: Chunk,
+}
+
+impl std::fmt::Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.name.is_empty() {
+            write!(f, "<script>")
+        } else {
+            write!(f, "<fn {}>", self.name)
+        }
+    }
+}
+
+// `count` consecutive bytes of code that come from `line`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRun {
+    pub line: u32,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<Constant>,
+    pub lines: Vec<LineRun>,
+    // (offset, span) pairs, an instruction has the span of the last pair at or before it
+    pub spans: Vec<(usize, Span)>,
+}
+
+impl Chunk {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write(&mut self, byte: u8, line: u32, span: Span) {
+        let offset = self.code.len();
+        self.code.push(byte);
+
+        match self.lines.last_mut() {
+            Some(run) if run.line == line => run.count += 1,
+            _ => self.lines.push(LineRun { line, count: 1 }),
+        }
+
+        if self.spans.last().map(|(_, last)| *last) != Some(span) {
+            self.spans.push((offset, span));
+        }
+    }
+
+    pub fn write_op(&mut self, op: OpCode, line: u32, span: Span) {
+        self.write(op as u8, line, span);
+    }
+
+    // the index of the new constant
+    pub fn add_constant(&mut self, constant: Constant) -> usize {
+        self.constants.push(constant);
+        self.constants.len() - 1
+    }
+
+    pub fn line_at(&self, offset: usize) -> u32 {
+        let mut end = 0;
+        for run in &self.lines {
+            end += run.count;
+            if offset < end {
+                return run.line;
+            }
+        }
+        self.lines.last().map_or(0, |run| run.line)
+    }
+
+    pub fn span_at(&self, offset: usize) -> Span {
+        let index = self.spans.partition_point(|(start, _)| *start <= offset);
+        index
+            .checked_sub(1)
+            .map_or(Span::default(), |index| self.spans[index].1)
+    }
+
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.code[offset], self.code[offset + 1]])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opcode_round_trip() {
+        for op in OpCode::ALL {
+            assert_eq!(OpCode::try_from(*op as u8), Ok(*op));
+        }
+        assert!(OpCode::try_from(OpCode::ALL.len() as u8).is_err());
+    }
+
+    #[test]
+    fn test_line_table_is_run_length_encoded() {
+        let mut chunk = Chunk::new();
+        chunk.write_op(OpCode::Nil, 1, Span::new(0, 3));
+        chunk.write_op(OpCode::Print, 1, Span::new(0, 3));
+        chunk.write_op(OpCode::True, 3, Span::new(10, 14));
+        chunk.write_op(OpCode::Return, 4, Span::new(20, 20));
+
+        assert_eq!(
+            chunk.lines,
+            vec![
+                LineRun { line: 1, count: 2 },
+                LineRun { line: 3, count: 1 },
+                LineRun { line: 4, count: 1 },
+            ]
+        );
+        assert_eq!(chunk.line_at(1), 1);
+        assert_eq!(chunk.line_at(2), 3);
+        assert_eq!(chunk.line_at(3), 4);
+
+        assert_eq!(chunk.spans.len(), 3);
+        assert_eq!(chunk.span_at(1), Span::new(0, 3));
+        assert_eq!(chunk.span_at(2), Span::new(10, 14));
+    }
+}
@@ -0,0 +1,777 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::chunk::{Chunk, Constant, Function, OpCode};
+use crate::diagnostics::codes;
+use crate::domain::{
+    token::Token, Expression, FunctionDeclaration, Literal, Span, Stmt, Symbol, TokenType,
+};
+
+/*
+    The Compiler turns the syntax tree into bytecode for the virtual machine,
+    one `Function` per Lox function with the top level script as the outermost.
+
+    Local variables live in stack slots, so the compiler tracks which names are
+    in scope the same way the Resolver does, and works out which locals a
+    closure captures from its enclosing functions (its upvalues). Everything it
+    cannot find is a global, looked up by name at runtime.
+
+    The program must have passed the Resolver, only limits of the bytecode
+    format are reported here.
+
+    Reference - https://craftinginterpreters.com/compiling-expressions.html
+*/
+
+const MAX_LOCALS: usize = 256;
+const MAX_UPVALUES: usize = 256;
+
+#[derive(Debug, Clone)]
+pub struct CompileError {
+    pub code: &'static str,
+    pub message: String,
+    pub line: u32,
+    pub span: Span,
+}
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[line {}] Error: {}", self.line, self.message)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FunctionKind {
+    Script,
+    Function,
+    Method,
+    Initializer,
+}
+
+struct Local {
+    name: Symbol,
+    // None until the initializer has run
+    depth: Option<usize>,
+    is_captured: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Upvalue {
+    // a slot of the enclosing function when local, otherwise one of its upvalues
+    index: u8,
+    is_local: bool,
+}
+
+struct FunctionState {
+    function: Function,
+    kind: FunctionKind,
+    locals: Vec<Local>,
+    upvalues: Vec<Upvalue>,
+    scope_depth: usize,
+    // constant pool indexes of the names already used in this chunk
+    names: HashMap<Symbol, u16>,
+}
+
+impl FunctionState {
+    fn new(kind: FunctionKind, name: String, arity: usize) -> Self {
+        // slot zero holds the function being called, or the receiver of a method
+        let receiver = match kind {
+            FunctionKind::Method | FunctionKind::Initializer => "this",
+            _ => "",
+        };
+
+        Self {
+            function: Function {
+                name,
+                arity,
+                ..Function::default()
+            },
+            kind,
+            locals: vec![Local {
+                name: Symbol::intern(receiver),
+                depth: Some(0),
+                is_captured: false,
+            }],
+            upvalues: Vec::new(),
+            scope_depth: 0,
+            names: HashMap::new(),
+        }
+    }
+}
+
+// where a variable lives, with the operand used to reach it
+enum Slot {
+    Local(u8),
+    Upvalue(u8),
+    Global(u16),
+}
+
+pub struct Compiler {
+    // byte offset at which each line starts, to map spans to lines
+    line_starts: Vec<usize>,
+    source_len: usize,
+    states: Vec<FunctionState>,
+    pub errors: Vec<CompileError>,
+}
+
+impl Compiler {
+    pub fn new(source: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+
+        Self {
+            line_starts,
+            source_len: source.len(),
+            states: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn compile(&mut self, statements: &[Stmt]) -> Rc<Function> {
+        self.states
+            .push(FunctionState::new(FunctionKind::Script, String::new(), 0));
+
+        for statement in statements {
+            self.statement(statement);
+        }
+
+        let end = Span::new(self.source_len, self.source_len);
+        let (function, _) = self.end_function(end);
+        Rc::new(function)
+    }
+
+    fn statement(&mut self, statement: &Stmt) {
+        match statement {
+            Stmt::Block { statements, span } => {
+                self.begin_scope();
+                for statement in statements {
+                    self.statement(statement);
+                }
+                self.end_scope(*span);
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } => self.class(name, superclass.as_ref(), methods),
+            Stmt::Expression { expression, span } => {
+                self.expression(expression);
+                self.emit_op(OpCode::Pop, *span);
+            }
+            Stmt::Function(declaration) => {
+                let name = &declaration.name;
+                let global = self.declare_variable(name);
+                // marked right away so the function can call itself
+                self.mark_initialized();
+                self.function(declaration, FunctionKind::Function);
+                self.define_variable(global, name.span);
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                span,
+            } => {
+                self.expression(condition);
+                let then_jump = self.emit_jump(OpCode::JumpIfFalse, condition.span());
+                self.emit_op(OpCode::Pop, condition.span());
+                self.statement(then_branch);
+
+                let else_jump = self.emit_jump(OpCode::Jump, *span);
+                self.patch_jump(then_jump, *span);
+                self.emit_op(OpCode::Pop, condition.span());
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+                self.patch_jump(else_jump, *span);
+            }
+            Stmt::Print { expression, span } => {
+                self.expression(expression);
+                self.emit_op(OpCode::Print, *span);
+            }
+            Stmt::Return { keyword, value, .. } => {
+                match value {
+                    Some(value) => self.expression(value),
+                    None => self.emit_implicit_return_value(keyword.span),
+                }
+                self.emit_op(OpCode::Return, keyword.span);
+            }
+            Stmt::Var {
+                name, initializer, ..
+            } => {
+                let global = self.declare_variable(name);
+                match initializer {
+                    Some(initializer) => self.expression(initializer),
+                    None => self.emit_op(OpCode::Nil, name.span),
+                }
+                self.define_variable(global, name.span);
+            }
+            Stmt::While {
+                condition,
+                body,
+                span,
+            } => {
+                let loop_start = self.chunk().code.len();
+                self.expression(condition);
+                let exit_jump = self.emit_jump(OpCode::JumpIfFalse, condition.span());
+                self.emit_op(OpCode::Pop, condition.span());
+                self.statement(body);
+                self.emit_loop(loop_start, *span);
+
+                self.patch_jump(exit_jump, *span);
+                self.emit_op(OpCode::Pop, condition.span());
+            }
+        }
+    }
+
+    fn class(
+        &mut self,
+        name: &Token,
+        superclass: Option<&Expression>,
+        methods: &[Rc<FunctionDeclaration>],
+    ) {
+        let name_constant = self.identifier_constant(&name.lexeme, name.span);
+        let global = self.declare_variable(name);
+        self.emit_op_u16(OpCode::Class, name_constant, name.span);
+        self.mark_initialized();
+        self.define_variable(global, name.span);
+
+        // the superclass is kept in a local named `super` that methods capture
+        let has_superclass = if let Some(Expression::Variable {
+            name: superclass_name,
+            ..
+        }) = superclass
+        {
+            self.load_variable(&superclass_name.lexeme, superclass_name.span);
+            self.begin_scope();
+            self.add_local(Symbol::intern("super"), superclass_name.span);
+            self.mark_initialized();
+
+            self.load_variable(&name.lexeme, name.span);
+            self.emit_op(OpCode::Inherit, superclass_name.span);
+            true
+        } else {
+            false
+        };
+
+        // the class stays on the stack while its methods are attached
+        self.load_variable(&name.lexeme, name.span);
+        for method in methods {
+            let constant = self.identifier_constant(&method.name.lexeme, method.name.span);
+            let kind = if method.name.lexeme == "init" {
+                FunctionKind::Initializer
+            } else {
+                FunctionKind::Method
+            };
+            self.function(method, kind);
+            self.emit_op_u16(OpCode::Method, constant, method.name.span);
+        }
+        self.emit_op(OpCode::Pop, name.span);
+
+        if has_superclass {
+            self.end_scope(name.span);
+        }
+    }
+
+    fn function(&mut self, declaration: &FunctionDeclaration, kind: FunctionKind) {
+        self.states.push(FunctionState::new(
+            kind,
+            declaration.name.lexeme.to_string(),
+            declaration.params.len(),
+        ));
+        self.begin_scope();
+
+        for param in &declaration.params {
+            self.declare_variable(param);
+            self.mark_initialized();
+        }
+        for statement in &declaration.body {
+            self.statement(statement);
+        }
+
+        let end = Span::new(declaration.span.end, declaration.span.end);
+        let (function, upvalues) = self.end_function(end);
+
+        let constant = self.make_constant(Constant::Function(Rc::new(function)), declaration.span);
+        self.emit_op_u16(OpCode::Closure, constant, declaration.span);
+        for upvalue in upvalues {
+            self.emit_byte(upvalue.is_local as u8, declaration.span);
+            self.emit_byte(upvalue.index, declaration.span);
+        }
+    }
+
+    // finishes the innermost function with an implicit return
+    fn end_function(&mut self, span: Span) -> (Function, Vec<Upvalue>) {
+        self.emit_implicit_return_value(span);
+        self.emit_op(OpCode::Return, span);
+
+        let mut state = self.states.pop().expect("compiling inside a function");
+        state.function.upvalue_count = state.upvalues.len();
+        (state.function, state.upvalues)
+    }
+
+    // initializers always return `this`, everything else `nil`
+    fn emit_implicit_return_value(&mut self, span: Span) {
+        if self.state().kind == FunctionKind::Initializer {
+            self.emit_op(OpCode::GetLocal, span);
+            self.emit_byte(0, span);
+        } else {
+            self.emit_op(OpCode::Nil, span);
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Assign { name, value, .. } => {
+                self.expression(value);
+                match self.resolve(&name.lexeme, name.span) {
+                    Slot::Local(slot) => self.emit_op_u8(OpCode::SetLocal, slot, name.span),
+                    Slot::Upvalue(index) => self.emit_op_u8(OpCode::SetUpvalue, index, name.span),
+                    Slot::Global(constant) => {
+                        self.emit_op_u16(OpCode::SetGlobal, constant, name.span)
+                    }
+                }
+            }
+            Expression::Binary {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                self.expression(left);
+                self.expression(right);
+
+                let span = operator.span;
+                match operator.token_type {
+                    TokenType::BangEqual => {
+                        self.emit_op(OpCode::Equal, span);
+                        self.emit_op(OpCode::Not, span);
+                    }
+                    TokenType::EqualEqual => self.emit_op(OpCode::Equal, span),
+                    TokenType::Greater => self.emit_op(OpCode::Greater, span),
+                    TokenType::GreaterEqual => self.emit_op(OpCode::GreaterEqual, span),
+                    TokenType::Less => self.emit_op(OpCode::Less, span),
+                    TokenType::LessEqual => self.emit_op(OpCode::LessEqual, span),
+                    TokenType::Plus => self.emit_op(OpCode::Add, span),
+                    TokenType::Minus => self.emit_op(OpCode::Subtract, span),
+                    TokenType::Star => self.emit_op(OpCode::Multiply, span),
+                    TokenType::Slash => self.emit_op(OpCode::Divide, span),
+                    _ => unreachable!("the parser only builds binary expressions from operators"),
+                }
+            }
+            Expression::Call {
+                callee,
+                paren,
+                arguments,
+                ..
+            } => {
+                self.expression(callee);
+                for argument in arguments {
+                    self.expression(argument);
+                }
+                // the parser already rejected calls with more than 255 arguments
+                self.emit_op_u8(OpCode::Call, arguments.len() as u8, paren.span);
+            }
+            Expression::Get { object, name, .. } => {
+                self.expression(object);
+                let constant = self.identifier_constant(&name.lexeme, name.span);
+                self.emit_op_u16(OpCode::GetProperty, constant, name.span);
+            }
+            Expression::Grouping { expression, .. } => self.expression(expression),
+            // each part is turned into a string and added to the ones before it
+            Expression::Interpolation { parts, .. } => {
+                for (index, part) in parts.iter().enumerate() {
+                    self.expression(part);
+                    if !matches!(
+                        part,
+                        Expression::Literal {
+                            value: Literal::String(_),
+                            ..
+                        }
+                    ) {
+                        self.emit_op(OpCode::Stringify, part.span());
+                    }
+                    if index > 0 {
+                        self.emit_op(OpCode::Add, part.span());
+                    }
+                }
+            }
+            Expression::Literal { value, span } => match value {
+                Literal::Number(number) => {
+                    let constant = self.make_constant(Constant::Number(*number), *span);
+                    self.emit_op_u16(OpCode::Constant, constant, *span);
+                }
+                Literal::String(string) => {
+                    let constant = self.identifier_constant(string, *span);
+                    self.emit_op_u16(OpCode::Constant, constant, *span);
+                }
+                Literal::Boolean(true) => self.emit_op(OpCode::True, *span),
+                Literal::Boolean(false) => self.emit_op(OpCode::False, *span),
+                Literal::Identifier(_) | Literal::Nil => self.emit_op(OpCode::Nil, *span),
+            },
+            // returns whichever operand decided the result, not a boolean
+            Expression::Logical {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                self.expression(left);
+                let span = operator.span;
+                if operator.token_type == TokenType::Or {
+                    let else_jump = self.emit_jump(OpCode::JumpIfFalse, span);
+                    let end_jump = self.emit_jump(OpCode::Jump, span);
+                    self.patch_jump(else_jump, span);
+                    self.emit_op(OpCode::Pop, span);
+                    self.expression(right);
+                    self.patch_jump(end_jump, span);
+                } else {
+                    let end_jump = self.emit_jump(OpCode::JumpIfFalse, span);
+                    self.emit_op(OpCode::Pop, span);
+                    self.expression(right);
+                    self.patch_jump(end_jump, span);
+                }
+            }
+            Expression::Set {
+                object,
+                name,
+                value,
+                ..
+            } => {
+                self.expression(object);
+                self.expression(value);
+                let constant = self.identifier_constant(&name.lexeme, name.span);
+                self.emit_op_u16(OpCode::SetProperty, constant, name.span);
+            }
+            Expression::Super {
+                keyword, method, ..
+            } => {
+                let constant = self.identifier_constant(&method.lexeme, method.span);
+                self.load_variable(&Symbol::intern("this"), keyword.span);
+                self.load_variable(&Symbol::intern("super"), keyword.span);
+                self.emit_op_u16(OpCode::GetSuper, constant, method.span);
+            }
+            Expression::This { keyword, .. } => {
+                self.load_variable(&Symbol::intern("this"), keyword.span)
+            }
+            Expression::Unary {
+                operator, right, ..
+            } => {
+                self.expression(right);
+                match operator.token_type {
+                    TokenType::Bang => self.emit_op(OpCode::Not, operator.span),
+                    TokenType::Minus => self.emit_op(OpCode::Negate, operator.span),
+                    _ => unreachable!("the parser only builds unary expressions from '!' and '-'"),
+                }
+            }
+            Expression::Variable { name, .. } => self.load_variable(&name.lexeme, name.span),
+        }
+    }
+
+    // helpers for variables and scopes
+
+    fn load_variable(&mut self, name: &Symbol, span: Span) {
+        match self.resolve(name, span) {
+            Slot::Local(slot) => self.emit_op_u8(OpCode::GetLocal, slot, span),
+            Slot::Upvalue(index) => self.emit_op_u8(OpCode::GetUpvalue, index, span),
+            Slot::Global(constant) => self.emit_op_u16(OpCode::GetGlobal, constant, span),
+        }
+    }
+
+    fn resolve(&mut self, name: &Symbol, span: Span) -> Slot {
+        let current = self.states.len() - 1;
+        if let Some(slot) = self.resolve_local(current, name) {
+            return Slot::Local(slot);
+        }
+        if let Some(index) = self.resolve_upvalue(current, name, span) {
+            return Slot::Upvalue(index);
+        }
+        Slot::Global(self.identifier_constant(name, span))
+    }
+
+    fn resolve_local(&self, state: usize, name: &Symbol) -> Option<u8> {
+        self.states[state]
+            .locals
+            .iter()
+            .rposition(|local| local.name == *name)
+            .map(|slot| slot as u8)
+    }
+
+    fn resolve_upvalue(&mut self, state: usize, name: &Symbol, span: Span) -> Option<u8> {
+        if state == 0 {
+            return None;
+        }
+
+        if let Some(slot) = self.resolve_local(state - 1, name) {
+            self.states[state - 1].locals[slot as usize].is_captured = true;
+            return Some(self.add_upvalue(state, slot, true, span));
+        }
+
+        let index = self.resolve_upvalue(state - 1, name, span)?;
+        Some(self.add_upvalue(state, index, false, span))
+    }
+
+    fn add_upvalue(&mut self, state: usize, index: u8, is_local: bool, span: Span) -> u8 {
+        let upvalue = Upvalue { index, is_local };
+        let upvalues = &self.states[state].upvalues;
+        if let Some(existing) = upvalues.iter().position(|known| *known == upvalue) {
+            return existing as u8;
+        }
+
+        if upvalues.len() == MAX_UPVALUES {
+            self.error(
+                codes::TOO_MANY_UPVALUES,
+                "Too many closure variables in function.",
+                span,
+            );
+            return 0;
+        }
+
+        self.states[state].upvalues.push(upvalue);
+        (self.states[state].upvalues.len() - 1) as u8
+    }
+
+    // locals are added to the current scope, globals return their name constant
+    fn declare_variable(&mut self, name: &Token) -> Option<u16> {
+        if self.state().scope_depth == 0 {
+            return Some(self.identifier_constant(&name.lexeme, name.span));
+        }
+
+        self.add_local(name.lexeme.clone(), name.span);
+        None
+    }
+
+    fn add_local(&mut self, name: Symbol, span: Span) {
+        if self.state().locals.len() == MAX_LOCALS {
+            self.error(
+                codes::TOO_MANY_LOCALS,
+                "Too many local variables in function.",
+                span,
+            );
+            return;
+        }
+
+        self.state_mut().locals.push(Local {
+            name,
+            depth: None,
+            is_captured: false,
+        });
+    }
+
+    fn mark_initialized(&mut self) {
+        let state = self.state_mut();
+        if state.scope_depth == 0 {
+            return;
+        }
+        let depth = state.scope_depth;
+        if let Some(local) = state.locals.last_mut() {
+            local.depth = Some(depth);
+        }
+    }
+
+    fn define_variable(&mut self, global: Option<u16>, span: Span) {
+        match global {
+            Some(constant) => self.emit_op_u16(OpCode::DefineGlobal, constant, span),
+            None => self.mark_initialized(),
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.state_mut().scope_depth += 1;
+    }
+
+    // pops the scope's locals, closing over the ones a closure captured
+    fn end_scope(&mut self, span: Span) {
+        self.state_mut().scope_depth -= 1;
+        let depth = self.state().scope_depth;
+
+        while let Some(local) = self.state().locals.last() {
+            if local.depth.is_some_and(|local_depth| local_depth <= depth) {
+                break;
+            }
+
+            let op = if local.is_captured {
+                OpCode::CloseUpvalue
+            } else {
+                OpCode::Pop
+            };
+            self.emit_op(op, span);
+            self.state_mut().locals.pop();
+        }
+    }
+
+    // helpers to write bytecode
+
+    fn state(&self) -> &FunctionState {
+        self.states.last().expect("compiling inside a function")
+    }
+
+    fn state_mut(&mut self) -> &mut FunctionState {
+        self.states.last_mut().expect("compiling inside a function")
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.state_mut().function.chunk
+    }
+
+    fn line(&self, span: Span) -> u32 {
+        self.line_starts
+            .partition_point(|start| *start <= span.start) as u32
+    }
+
+    fn emit_byte(&mut self, byte: u8, span: Span) {
+        let line = self.line(span);
+        self.chunk().write(byte, line, span);
+    }
+
+    fn emit_op(&mut self, op: OpCode, span: Span) {
+        self.emit_byte(op as u8, span);
+    }
+
+    fn emit_op_u8(&mut self, op: OpCode, operand: u8, span: Span) {
+        self.emit_op(op, span);
+        self.emit_byte(operand, span);
+    }
+
+    fn emit_op_u16(&mut self, op: OpCode, operand: u16, span: Span) {
+        self.emit_op(op, span);
+        for byte in operand.to_be_bytes() {
+            self.emit_byte(byte, span);
+        }
+    }
+
+    // returns the offset of the jump's operand, to be patched later
+    fn emit_jump(&mut self, op: OpCode, span: Span) -> usize {
+        self.emit_op_u16(op, u16::MAX, span);
+        self.chunk().code.len() - 2
+    }
+
+    fn patch_jump(&mut self, offset: usize, span: Span) {
+        let jump = self.chunk().code.len() - offset - 2;
+        let Ok(jump) = u16::try_from(jump) else {
+            self.error(codes::JUMP_TOO_LARGE, "Too much code to jump over.", span);
+            return;
+        };
+
+        let [high, low] = jump.to_be_bytes();
+        self.chunk().code[offset] = high;
+        self.chunk().code[offset + 1] = low;
+    }
+
+    fn emit_loop(&mut self, loop_start: usize, span: Span) {
+        self.emit_op(OpCode::Loop, span);
+        let offset = self.chunk().code.len() - loop_start + 2;
+        let offset = u16::try_from(offset).unwrap_or_else(|_| {
+            self.error(codes::JUMP_TOO_LARGE, "Loop body too large.", span);
+            0
+        });
+        for byte in offset.to_be_bytes() {
+            self.emit_byte(byte, span);
+        }
+    }
+
+    fn make_constant(&mut self, constant: Constant, span: Span) -> u16 {
+        let index = self.chunk().add_constant(constant);
+        u16::try_from(index).unwrap_or_else(|_| {
+            self.error(
+                codes::TOO_MANY_CONSTANTS,
+                "Too many constants in one chunk.",
+                span,
+            );
+            0
+        })
+    }
+
+    // names and string literals share one constant per chunk
+    fn identifier_constant(&mut self, name: &Symbol, span: Span) -> u16 {
+        if let Some(constant) = self.state().names.get(name) {
+            return *constant;
+        }
+
+        let constant = self.make_constant(Constant::String(name.to_string()), span);
+        self.state_mut().names.insert(name.clone(), constant);
+        constant
+    }
+
+    fn error(&mut self, code: &'static str, message: &str, span: Span) {
+        let line = self.line(span);
+        self.errors.push(CompileError {
+            code,
+            message: message.to_string(),
+            line,
+            span,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn compile(source: &str) -> (Rc<Function>, Vec<CompileError>) {
+        let statements = Parser::new(Scanner::new(source)).parse();
+
+        let mut compiler = Compiler::new(source);
+        let function = compiler.compile(&statements);
+        (function, compiler.errors)
+    }
+
+    #[test]
+    fn test_compile_expression_statement() {
+        let (function, errors) = compile("print 1 + 2;");
+        assert!(errors.is_empty());
+        assert_eq!(
+            function.chunk.code,
+            vec![
+                OpCode::Constant as u8,
+                0,
+                0,
+                OpCode::Constant as u8,
+                0,
+                1,
+                OpCode::Add as u8,
+                OpCode::Print as u8,
+                OpCode::Nil as u8,
+                OpCode::Return as u8,
+            ]
+        );
+        assert_eq!(
+            function.chunk.constants,
+            vec![Constant::Number(1.0), Constant::Number(2.0)]
+        );
+    }
+
+    #[test]
+    fn test_compile_closure_captures_enclosing_local() {
+        let (function, errors) =
+            compile("fun outer() {\n var a = 1;\n fun inner() { return a; }\n}");
+        assert!(errors.is_empty());
+
+        let Constant::Function(outer) = &function.chunk.constants[1] else {
+            panic!("expected the function constant");
+        };
+        let inner = outer
+            .chunk
+            .constants
+            .iter()
+            .find_map(|constant| match constant {
+                Constant::Function(inner) => Some(inner),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(inner.name, "inner");
+        assert_eq!(inner.upvalue_count, 1);
+        assert_eq!(outer.chunk.line_at(outer.chunk.code.len() - 1), 4);
+    }
+
+    #[test]
+    fn test_compile_too_many_locals() {
+        let locals: String = (0..MAX_LOCALS).map(|i| format!("var a{};", i)).collect();
+        let (_, errors) = compile(&format!("{{ {} }}", locals));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Too many local variables in function.");
+    }
+}
@@ -0,0 +1,452 @@
+/*
+    Every error the interpreter can report has a stable code, like `L0001`.
+    Codes are never reused or renumbered, new errors get the next free one.
+
+    Each stage sets the code on an error where it builds it, from the
+    constants below, so rewording a message never changes its code. Errors
+    raised by native functions have none.
+
+    `explain <code>` prints the long form, with a Lox example that triggers
+    the error and one that fixes it.
+*/
+
+// scanner
+pub const UNEXPECTED_CHARACTER: &str = "L0001";
+pub const UNTERMINATED_STRING: &str = "L0002";
+
+// parser
+pub const UNCLOSED_PARENTHESIS: &str = "L0003";
+pub const EXPECTED_EXPRESSION: &str = "L0004";
+pub const MISSING_SEMICOLON: &str = "L0005";
+pub const MISSING_DELIMITER: &str = "L0006";
+pub const EXPECTED_NAME: &str = "L0007";
+pub const INVALID_ASSIGNMENT_TARGET: &str = "L0008";
+pub const TOO_MANY_ARGUMENTS: &str = "L0009";
+
+// resolver
+pub const READ_IN_OWN_INITIALIZER: &str = "L0010";
+pub const ALREADY_DECLARED: &str = "L0011";
+pub const TOP_LEVEL_RETURN: &str = "L0012";
+pub const RETURN_FROM_INITIALIZER: &str = "L0013";
+pub const THIS_OUTSIDE_CLASS: &str = "L0014";
+pub const INVALID_SUPER: &str = "L0015";
+pub const INHERITS_FROM_ITSELF: &str = "L0016";
+
+// runtime
+pub const OPERAND_NOT_A_NUMBER: &str = "L0017";
+pub const INVALID_PLUS_OPERANDS: &str = "L0018";
+pub const UNDEFINED_VARIABLE: &str = "L0019";
+pub const UNDEFINED_PROPERTY: &str = "L0020";
+pub const NOT_AN_INSTANCE: &str = "L0021";
+pub const NOT_CALLABLE: &str = "L0022";
+pub const WRONG_ARGUMENT_COUNT: &str = "L0023";
+pub const SUPERCLASS_NOT_A_CLASS: &str = "L0024";
+pub const STACK_OVERFLOW: &str = "L0025";
+
+// bytecode compiler
+pub const TOO_MANY_LOCALS: &str = "L0026";
+pub const TOO_MANY_UPVALUES: &str = "L0027";
+pub const JUMP_TOO_LARGE: &str = "L0028";
+pub const TOO_MANY_CONSTANTS: &str = "L0029";
+
+// scanner
+pub const INVALID_ESCAPE: &str = "L0030";
+pub const UNTERMINATED_INTERPOLATION: &str = "L0031";
+pub const UNTERMINATED_BLOCK_COMMENT: &str = "L0032";
+
+pub struct ErrorCode {
+    pub code: &'static str,
+    pub title: &'static str,
+    pub explanation: &'static str,
+    pub bad: &'static str,
+    pub good: &'static str,
+}
+
+pub const CODES: &[ErrorCode] = &[
+    // scanner
+    ErrorCode {
+        code: UNEXPECTED_CHARACTER,
+        title: "Unexpected character",
+        explanation: "The scanner found a character that doesn't start any Lox token. \
+Outside of strings and comments only letters, digits, `_`, whitespace and the \
+operators and punctuation of the language are allowed.",
+        bad: "var total = 10 $ 2;",
+        good: "var total = 10 * 2;",
+    },
+    ErrorCode {
+        code: UNTERMINATED_STRING,
+        title: "Unterminated string",
+        explanation: "A string literal was opened with `\"` but the file ended before \
+the closing `\"`. Strings may span several lines, so the missing quote can be far \
+from where the error is reported.",
+        bad: "print \"hello;",
+        good: "print \"hello\";",
+    },
+    // parser
+    ErrorCode {
+        code: UNCLOSED_PARENTHESIS,
+        title: "Unclosed parenthesis",
+        explanation: "A parenthesized expression was opened with `(` but the next token \
+after the expression inside it isn't `)`.",
+        bad: "print (1 + 2;",
+        good: "print (1 + 2);",
+    },
+    ErrorCode {
+        code: EXPECTED_EXPRESSION,
+        title: "Expected an expression",
+        explanation: "The parser needed a value here, such as a literal, a variable, \
+a call or a parenthesized expression, but found something else. This usually \
+means an operand is missing on one side of an operator.",
+        bad: "var a = 1 + ;",
+        good: "var a = 1 + 2;",
+    },
+    ErrorCode {
+        code: MISSING_SEMICOLON,
+        title: "Missing semicolon",
+        explanation: "Every statement that isn't a block, or a class, function, `if`, \
+`while` or `for` with its own body, has to end with `;`.",
+        bad: "var a = 1\nprint a;",
+        good: "var a = 1;\nprint a;",
+    },
+    ErrorCode {
+        code: MISSING_DELIMITER,
+        title: "Missing delimiter",
+        explanation: "A statement is missing one of the brackets or dots its syntax \
+requires: conditions of `if`, `while` and `for` go in parentheses, bodies of \
+functions and classes go in braces, and `super` must be followed by `.method`.",
+        bad: "var a = 2;\nif a > 1 print a;",
+        good: "var a = 2;\nif (a > 1) print a;",
+    },
+    ErrorCode {
+        code: EXPECTED_NAME,
+        title: "Expected a name",
+        explanation: "A declaration, parameter list or property access needs an \
+identifier here. Keywords like `class` or `var` can't be used as names.",
+        bad: "var class = 1;",
+        good: "var kind = 1;",
+    },
+    ErrorCode {
+        code: INVALID_ASSIGNMENT_TARGET,
+        title: "Invalid assignment target",
+        explanation: "Only variables and object fields can be assigned to. The left \
+side of `=` was some other expression, like a call or an arithmetic operation.",
+        bad: "var a = 1;\na + 1 = 3;",
+        good: "var a = 1;\na = 3 - 1;",
+    },
+    ErrorCode {
+        code: TOO_MANY_ARGUMENTS,
+        title: "Too many arguments or parameters",
+        explanation: "A function can take at most 255 parameters, and a call can pass \
+at most 255 arguments. Group related values into an instance instead.",
+        bad: "fun f(a1, a2, a3, // and so on\n      a256) {}",
+        good: "class Options {}\nfun f(options) {}",
+    },
+    // resolver
+    ErrorCode {
+        code: READ_IN_OWN_INITIALIZER,
+        title: "Local variable read in its own initializer",
+        explanation: "A local variable can't be used in the expression that gives it \
+its first value, since it doesn't have one yet. Globals are allowed to, to keep \
+the REPL forgiving.",
+        bad: "{\n  var a = a + 1;\n}",
+        good: "{\n  var b = 1;\n  var a = b + 1;\n}",
+    },
+    ErrorCode {
+        code: ALREADY_DECLARED,
+        title: "Variable declared twice in one scope",
+        explanation: "Inside a block or function, each name can only be declared \
+once per scope. Assign to the existing variable instead, or open a new block \
+to shadow it.",
+        bad: "fun f() {\n  var a = 1;\n  var a = 2;\n}",
+        good: "fun f() {\n  var a = 1;\n  a = 2;\n}",
+    },
+    ErrorCode {
+        code: TOP_LEVEL_RETURN,
+        title: "Return outside of a function",
+        explanation: "`return` only makes sense inside a function or method body.",
+        bad: "return 1;",
+        good: "fun one() {\n  return 1;\n}",
+    },
+    ErrorCode {
+        code: RETURN_FROM_INITIALIZER,
+        title: "Value returned from an initializer",
+        explanation: "`init` always returns the new instance, so it can't return \
+anything else. A bare `return;` is still allowed to leave it early.",
+        bad: "class Point {\n  init(x) {\n    return x;\n  }\n}",
+        good: "class Point {\n  init(x) {\n    this.x = x;\n    return;\n  }\n}",
+    },
+    ErrorCode {
+        code: THIS_OUTSIDE_CLASS,
+        title: "'this' outside of a class",
+        explanation: "`this` refers to the instance a method was called on, so it can \
+only appear inside the methods of a class.",
+        bad: "fun name() {\n  return this.name;\n}",
+        good: "class Person {\n  name() {\n    return this.name;\n  }\n}",
+    },
+    ErrorCode {
+        code: INVALID_SUPER,
+        title: "Invalid use of 'super'",
+        explanation: "`super` calls a method of the superclass, so it can only appear \
+inside the methods of a class declared with `<`.",
+        bad: "class Dog {\n  speak() {\n    super.speak();\n  }\n}",
+        good: "class Animal {\n  speak() {}\n}\nclass Dog < Animal {\n  speak() {\n    super.speak();\n  }\n}",
+    },
+    ErrorCode {
+        code: INHERITS_FROM_ITSELF,
+        title: "Class inherits from itself",
+        explanation: "The superclass named after `<` must be a different class.",
+        bad: "class Node < Node {}",
+        good: "class Base {}\nclass Node < Base {}",
+    },
+    // runtime
+    ErrorCode {
+        code: OPERAND_NOT_A_NUMBER,
+        title: "Operand must be a number",
+        explanation: "Negation, comparisons and arithmetic other than `+` only work on \
+numbers. Lox never converts other values to numbers for you.",
+        bad: "print -\"3\";",
+        good: "print -3;",
+    },
+    ErrorCode {
+        code: INVALID_PLUS_OPERANDS,
+        title: "Invalid operands to '+'",
+        explanation: "`+` adds two numbers or concatenates two strings. Mixing a number \
+and a string, or using any other value, is an error.",
+        bad: "print \"total: \" + 3;",
+        good: "print \"total: \" + \"3\";",
+    },
+    ErrorCode {
+        code: UNDEFINED_VARIABLE,
+        title: "Undefined variable",
+        explanation: "The variable was read or assigned before any declaration of it \
+was executed. Check the spelling, and that the `var` runs first.",
+        bad: "print count;\nvar count = 1;",
+        good: "var count = 1;\nprint count;",
+    },
+    ErrorCode {
+        code: UNDEFINED_PROPERTY,
+        title: "Undefined property",
+        explanation: "The instance has no field with this name, and its class and \
+superclasses have no method with it.",
+        bad: "class Box {}\nprint Box().size;",
+        good: "class Box {}\nvar box = Box();\nbox.size = 1;\nprint box.size;",
+    },
+    ErrorCode {
+        code: NOT_AN_INSTANCE,
+        title: "Property access on a non-instance",
+        explanation: "Only instances of classes have properties. Numbers, strings, \
+booleans, nil, functions and classes themselves don't.",
+        bad: "var name = \"lox\";\nprint name.length;",
+        good: "class Name {}\nvar name = Name();\nname.length = 3;\nprint name.length;",
+    },
+    ErrorCode {
+        code: NOT_CALLABLE,
+        title: "Calling a non-callable value",
+        explanation: "Only functions, methods, native functions and classes can be \
+called with `()`.",
+        bad: "var greeting = \"hi\";\ngreeting();",
+        good: "fun greeting() {\n  print \"hi\";\n}\ngreeting();",
+    },
+    ErrorCode {
+        code: WRONG_ARGUMENT_COUNT,
+        title: "Wrong number of arguments",
+        explanation: "A function must be called with exactly as many arguments as it \
+has parameters. A class is called with the arguments of its `init` method.",
+        bad: "fun add(a, b) {\n  return a + b;\n}\nprint add(1);",
+        good: "fun add(a, b) {\n  return a + b;\n}\nprint add(1, 2);",
+    },
+    ErrorCode {
+        code: SUPERCLASS_NOT_A_CLASS,
+        title: "Superclass must be a class",
+        explanation: "The name after `<` has to refer to a class when the class \
+declaration runs.",
+        bad: "var Base = \"base\";\nclass Derived < Base {}",
+        good: "class Base {}\nclass Derived < Base {}",
+    },
+    ErrorCode {
+        code: STACK_OVERFLOW,
+        title: "Stack overflow",
+        explanation: "The program made too many nested calls without returning, \
+usually because a recursive function has no base case or never reaches it.",
+        bad: "fun count(n) {\n  return count(n + 1);\n}\ncount(0);",
+        good: "fun count(n) {\n  if (n == 10) return n;\n  return count(n + 1);\n}\ncount(0);",
+    },
+    // bytecode compiler
+    ErrorCode {
+        code: TOO_MANY_LOCALS,
+        title: "Too many local variables",
+        explanation: "The bytecode backend keeps at most 256 local variables in scope \
+in one function, parameters included. Split the function up, or move some of \
+the variables into nested blocks that end before the others are declared.",
+        bad: "fun f() {\n  var a1; var a2; // and so on\n  var a256;\n}",
+        good: "fun f() {\n  { var a1; var a2; }\n  { var a3; var a4; }\n}",
+    },
+    ErrorCode {
+        code: TOO_MANY_UPVALUES,
+        title: "Too many closure variables",
+        explanation: "The bytecode backend lets a function capture at most 256 variables \
+of the functions around it. Pass some of them as arguments instead.",
+        bad: "var a1; // and so on\nfun f() {\n  var b1; // and so on\n  fun g() { return b1; }\n}",
+        good: "fun g(b1) {\n  return b1;\n}",
+    },
+    ErrorCode {
+        code: JUMP_TOO_LARGE,
+        title: "Jump too large",
+        explanation: "The bytecode backend can only jump over 65535 bytes of code, so \
+the branch of an `if`, the right side of `and` or `or`, or the body of a loop \
+is too long. Move part of it into a function.",
+        bad: "var running = false;\nwhile (running) {\n  // thousands of statements\n}",
+        good: "var running = false;\nfun step() {\n  // thousands of statements\n}\nwhile (running) step();",
+    },
+    ErrorCode {
+        code: TOO_MANY_CONSTANTS,
+        title: "Too many constants",
+        explanation: "The bytecode backend stores at most 65536 distinct names, strings \
+and numbers per function. Move part of the code into other functions.",
+        bad: "print 1; print 2; // and so on\nprint 65537;",
+        good: "fun first() {\n  print 1; // and so on\n}\nfirst();",
+    },
+    // scanner
+    ErrorCode {
+        code: INVALID_ESCAPE,
+        title: "Invalid escape sequence",
+        explanation: "A backslash in a string starts an escape sequence, and only \
+`\\n`, `\\t`, `\\r`, `\\\\`, `\\\"`, `\\0`, `\\$` and `\\u{...}` exist. A `\\u{...}` escape takes \
+one to six hex digits naming a Unicode scalar value. To write a backslash itself, \
+escape it.",
+        bad: "print \"C:\\dir\";",
+        good: "print \"C:\\\\dir\";",
+    },
+    ErrorCode {
+        code: UNTERMINATED_INTERPOLATION,
+        title: "Unterminated interpolation",
+        explanation: "A `${` inside a string starts an interpolated expression, which \
+runs until the matching `}`. The source ended before that `}` was found. A literal \
+`${` is written by escaping the dollar sign, as in `\"\\${\"`.",
+        bad: "var price = 1;\nprint \"total: ${price * 2\";",
+        good: "var price = 1;\nprint \"total: ${price * 2}\";",
+    },
+    ErrorCode {
+        code: UNTERMINATED_BLOCK_COMMENT,
+        title: "Unterminated block comment",
+        explanation: "A block comment was opened with `/*` but the file ended before \
+its closing `*/`. Block comments nest, so a `/*` inside a comment needs a `*/` of its \
+own. The error points at the comment that was never closed.",
+        bad: "/* outer /* inner */\nprint 1;",
+        good: "/* outer /* inner */ */\nprint 1;",
+    },
+];
+
+pub fn find(code: &str) -> Option<&'static ErrorCode> {
+    CODES
+        .iter()
+        .find(|entry| entry.code.eq_ignore_ascii_case(code))
+}
+
+fn indent(source: &str) -> String {
+    source
+        .lines()
+        .map(|line| format!("    {}\n", line))
+        .collect()
+}
+
+// the long form printed by `explain <code>`
+pub fn explain(code: &str) -> Option<String> {
+    let entry = find(code)?;
+    Some(format!(
+        "{}: {}\n\n{}\n\nErroneous code example:\n\n{}\nCorrected example:\n\n{}",
+        entry.code,
+        entry.title,
+        entry.explanation,
+        indent(entry.bad),
+        indent(entry.good)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use crate::interpreter::Interpreter;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+    use std::collections::HashSet;
+    use std::io;
+
+    #[test]
+    fn test_codes_are_unique() {
+        let codes: HashSet<&str> = CODES.iter().map(|entry| entry.code).collect();
+        assert_eq!(codes.len(), CODES.len());
+    }
+
+    #[test]
+    fn test_every_code_can_be_explained() {
+        for code in [
+            UNEXPECTED_CHARACTER,
+            UNTERMINATED_STRING,
+            UNCLOSED_PARENTHESIS,
+            EXPECTED_EXPRESSION,
+            MISSING_SEMICOLON,
+            MISSING_DELIMITER,
+            EXPECTED_NAME,
+            INVALID_ASSIGNMENT_TARGET,
+            TOO_MANY_ARGUMENTS,
+            READ_IN_OWN_INITIALIZER,
+            ALREADY_DECLARED,
+            TOP_LEVEL_RETURN,
+            RETURN_FROM_INITIALIZER,
+            THIS_OUTSIDE_CLASS,
+            INVALID_SUPER,
+            INHERITS_FROM_ITSELF,
+            OPERAND_NOT_A_NUMBER,
+            INVALID_PLUS_OPERANDS,
+            UNDEFINED_VARIABLE,
+            UNDEFINED_PROPERTY,
+            NOT_AN_INSTANCE,
+            NOT_CALLABLE,
+            WRONG_ARGUMENT_COUNT,
+            SUPERCLASS_NOT_A_CLASS,
+            STACK_OVERFLOW,
+            TOO_MANY_LOCALS,
+            TOO_MANY_UPVALUES,
+            JUMP_TOO_LARGE,
+            TOO_MANY_CONSTANTS,
+            INVALID_ESCAPE,
+            UNTERMINATED_INTERPOLATION,
+            UNTERMINATED_BLOCK_COMMENT,
+        ] {
+            assert_eq!(find(code).map(|entry| entry.code), Some(code));
+        }
+    }
+
+    #[test]
+    fn test_good_examples_run() {
+        for entry in CODES {
+            let mut parser = Parser::new(Scanner::new(entry.good));
+            let statements = parser.parse();
+            assert!(parser.errors.is_empty(), "{} doesn't parse", entry.code);
+
+            let mut resolver = Resolver::new();
+            resolver.resolve(&statements);
+            assert!(resolver.errors.is_empty(), "{} doesn't resolve", entry.code);
+
+            let mut compiler = Compiler::new(entry.good);
+            compiler.compile(&statements);
+            assert!(compiler.errors.is_empty(), "{} doesn't compile", entry.code);
+
+            let mut interpreter = Interpreter::with_output(Box::new(io::sink()));
+            interpreter.resolve(resolver.locals);
+            let error = interpreter.interpret(&statements).err();
+            assert!(error.is_none(), "{} fails with {:?}", entry.code, error);
+        }
+    }
+
+    #[test]
+    fn test_explain() {
+        let explanation = explain("l0002").unwrap();
+        assert!(explanation.starts_with("L0002: Unterminated string\n"));
+        assert!(explanation.contains("    print \"hello;\n"));
+        assert!(explain("L9999").is_none());
+    }
+}
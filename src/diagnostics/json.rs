@@ -0,0 +1,130 @@
+use std::fmt::Write;
+
+use super::render::location;
+use super::Diagnostic;
+
+/*
+    Encodes a Diagnostic as a single line of JSON, for editors and other tools:
+
+        {"file":"main.lox","line":1,"column":5,"span":{"start":4,"end":5},
+         "severity":"error","code":null,"message":"Unexpected character: $"}
+
+    Labels and notes are included as arrays. Without the source, as when
+    running a compiled script, the line is the one the error reports and the
+    columns are null. The encoder is written by hand, everything here is a
+    string, a number or null.
+*/
+
+pub fn to_json(diagnostic: &Diagnostic, filename: &str, source: Option<&str>) -> String {
+    let (line, column) = match source {
+        Some(source) => position(source, diagnostic.span.start),
+        None => (
+            diagnostic
+                .line
+                .map_or("null".to_string(), |line| line.to_string()),
+            "null".to_string(),
+        ),
+    };
+    let mut out = String::new();
+
+    out.push('{');
+    let _ = write!(out, "\"file\":{},", string(filename));
+    let _ = write!(out, "\"line\":{},\"column\":{},", line, column);
+    let _ = write!(
+        out,
+        "\"span\":{{\"start\":{},\"end\":{}}},",
+        diagnostic.span.start, diagnostic.span.end
+    );
+    let _ = write!(
+        out,
+        "\"severity\":{},",
+        string(&diagnostic.severity.to_string())
+    );
+    match &diagnostic.code {
+        Some(code) => {
+            let _ = write!(out, "\"code\":{},", string(code));
+        }
+        None => out.push_str("\"code\":null,"),
+    }
+    let _ = write!(out, "\"message\":{},", string(&diagnostic.message));
+
+    out.push_str("\"labels\":[");
+    for (index, label) in diagnostic.labels.iter().enumerate() {
+        if index > 0 {
+            out.push(',');
+        }
+        let (line, column) = match source {
+            Some(source) => position(source, label.span.start),
+            None => ("null".to_string(), "null".to_string()),
+        };
+        let _ = write!(
+            out,
+            "{{\"line\":{},\"column\":{},\"span\":{{\"start\":{},\"end\":{}}},\"message\":{}}}",
+            line,
+            column,
+            label.span.start,
+            label.span.end,
+            string(&label.message)
+        );
+    }
+    out.push_str("],");
+
+    out.push_str("\"notes\":[");
+    let notes: Vec<String> = diagnostic.notes.iter().map(|note| string(note)).collect();
+    out.push_str(&notes.join(","));
+    out.push_str("]}");
+
+    out
+}
+
+// the line and column of an offset, as JSON numbers
+fn position(source: &str, offset: usize) -> (String, String) {
+    let (line, column) = location(source, offset);
+    (line.to_string(), column.to_string())
+}
+
+// a JSON string literal, with quotes, backslashes and control characters escaped
+fn string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Span;
+
+    #[test]
+    fn test_to_json() {
+        let source = "var a;\nprint \"a\" - 1;";
+        let start = source.find('-').unwrap();
+        let diagnostic = Diagnostic::error(
+            "Operand must be a \"number\".".to_string(),
+            Span::new(start, start + 1),
+        )
+        .with_note("tab\there".to_string());
+
+        assert_eq!(
+            to_json(&diagnostic, "dir\\main.lox", Some(source)),
+            "{\"file\":\"dir\\\\main.lox\",\"line\":2,\"column\":11,\
+             \"span\":{\"start\":17,\"end\":18},\"severity\":\"error\",\"code\":null,\
+             \"message\":\"Operand must be a \\\"number\\\".\",\"labels\":[],\
+             \"notes\":[\"tab\\there\"]}"
+        );
+    }
+}
@@ -0,0 +1,419 @@
+use std::io::{self, IsTerminal};
+
+use crate::compiler::CompileError;
+use crate::domain::{Span, TokenType};
+use crate::interpreter::RuntimeError;
+use crate::parser::{ParserError, SyntaxError};
+use crate::resolver::ResolverError;
+use crate::scanner::ScannerError;
+
+pub mod codes;
+
+pub mod json;
+pub use json::to_json;
+
+pub mod render;
+pub use render::render;
+
+/*
+    A Diagnostic is the common shape of every error the interpreter reports,
+    whichever stage it comes from.
+
+    The `Emitter` prints diagnostics either in the plain `[line N] Error: ...`
+    format the CodeCrafters tests expect, rendered with the offending source
+    lines like rustc does, or as one JSON object per line for tools.
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+        }
+    }
+}
+
+// a span of source with an explanation, shown underneath the snippet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Label {
+    pub fn new(span: Span, message: String) -> Self {
+        Self { span, message }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<String>,
+    pub message: String,
+    // where the problem is, always underlined
+    pub span: Span,
+    // the line as the error reports it, for when the source isn't available
+    pub line: Option<u32>,
+    // extra spans that help explain the problem
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: String, span: Span) -> Self {
+        Self {
+            severity,
+            code: None,
+            message,
+            span,
+            line: None,
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn error(message: String, span: Span) -> Self {
+        Self::new(Severity::Error, message, span)
+    }
+
+    pub fn with_code(mut self, code: &str) -> Self {
+        self.code = Some(code.to_string());
+        self
+    }
+
+    pub fn with_line(mut self, line: u32) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, message: String) -> Self {
+        self.labels.push(Label::new(span, message));
+        self
+    }
+
+    pub fn with_note(mut self, note: String) -> Self {
+        self.notes.push(note);
+        self
+    }
+}
+
+// Errors that can be reported as a Diagnostic. Display stays the plain format.
+pub trait ToDiagnostic: std::fmt::Display {
+    fn to_diagnostic(&self) -> Diagnostic;
+}
+
+impl ToDiagnostic for ScannerError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        let diagnostic = Diagnostic::error(self.message.clone(), self.span)
+            .with_code(self.code)
+            .with_line(self.line);
+        if self.code == codes::UNTERMINATED_STRING {
+            return diagnostic.with_note("strings must end with a closing '\"'".to_string());
+        }
+        diagnostic
+    }
+}
+
+impl ToDiagnostic for ParserError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        let label = if self.token.token_type == TokenType::Eof {
+            "unexpected end of input".to_string()
+        } else {
+            format!("found '{}'", self.token.lexeme)
+        };
+        Diagnostic::error(self.message.clone(), self.token.span)
+            .with_code(self.code)
+            .with_line(self.token.line)
+            .with_label(self.token.span, label)
+    }
+}
+
+impl ToDiagnostic for SyntaxError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            SyntaxError::Scanner(error) => error.to_diagnostic(),
+            SyntaxError::Parser(error) => error.to_diagnostic(),
+        }
+    }
+}
+
+impl ToDiagnostic for ResolverError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic::error(self.message.clone(), self.token.span)
+            .with_code(self.code)
+            .with_line(self.token.line)
+    }
+}
+
+impl ToDiagnostic for CompileError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic::error(self.message.clone(), self.span)
+            .with_code(self.code)
+            .with_line(self.line)
+    }
+}
+
+impl ToDiagnostic for RuntimeError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        let diagnostic =
+            Diagnostic::error(self.message.clone(), self.token.span).with_line(self.token.line);
+        match self.code {
+            Some(code) => diagnostic.with_code(code),
+            None => diagnostic,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    // `[line N] Error: message`, as the CodeCrafters tests expect
+    Plain,
+    // rustc-style, with the source line and a caret underline
+    Human,
+    // one JSON object per diagnostic and line
+    Json,
+}
+
+impl std::str::FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "plain" => Ok(ErrorFormat::Plain),
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(format!(
+                "Unknown error format '{}', expected 'plain', 'human' or 'json'.",
+                format
+            )),
+        }
+    }
+}
+
+// writes diagnostics for one source file to stderr
+pub struct Emitter<'a> {
+    pub format: ErrorFormat,
+    pub filename: &'a str,
+    // None for compiled scripts, which are run without their source
+    pub source: Option<&'a str>,
+    pub color: bool,
+}
+
+impl<'a> Emitter<'a> {
+    // color is only used when stderr is a terminal and NO_COLOR is not set
+    pub fn new(format: ErrorFormat, filename: &'a str, source: &'a str) -> Self {
+        let color = io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+        Self {
+            format,
+            filename,
+            source: Some(source),
+            color,
+        }
+    }
+
+    // errors can only point at a line, there is no source to show
+    pub fn without_source(format: ErrorFormat, filename: &'a str) -> Self {
+        Self {
+            source: None,
+            ..Self::new(format, filename, "")
+        }
+    }
+
+    pub fn emit(&self, error: &dyn ToDiagnostic) {
+        match self.format {
+            ErrorFormat::Plain => eprintln!("{}", error),
+            ErrorFormat::Human => eprint!(
+                "{}",
+                render(
+                    &error.to_diagnostic(),
+                    self.filename,
+                    self.source.unwrap_or_default(),
+                    self.color
+                )
+            ),
+            ErrorFormat::Json => eprintln!(
+                "{}",
+                to_json(&error.to_diagnostic(), self.filename, self.source)
+            ),
+        }
+    }
+
+    pub fn emit_all<E: ToDiagnostic>(&self, errors: &[E]) {
+        for error in errors {
+            self.emit(error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use crate::interpreter::{Interpreter, Value};
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+    use crate::serializer::{deserialize, serialize};
+    use crate::vm::Vm;
+
+    // the code of the first error reported for a script, by whichever stage
+    // reports it, runtime errors have to agree between both backends
+    fn first_code(source: &str) -> Option<&'static str> {
+        let mut parser = Parser::new(Scanner::new(source));
+        let statements = parser.parse();
+        if let Some(error) = parser.errors.first() {
+            return Some(match error {
+                SyntaxError::Scanner(error) => error.code,
+                SyntaxError::Parser(error) => error.code,
+            });
+        }
+
+        let mut resolver = Resolver::new();
+        resolver.resolve(&statements);
+        if let Some(error) = resolver.errors.first() {
+            return Some(error.code);
+        }
+
+        let mut compiler = Compiler::new(source);
+        let script = compiler.compile(&statements);
+        if let Some(error) = compiler.errors.first() {
+            return Some(error.code);
+        }
+
+        let mut interpreter = Interpreter::with_output(Box::new(io::sink()));
+        interpreter.resolve(resolver.locals);
+        let code = interpreter.interpret(&statements).err()?.code;
+
+        let vm_error = Vm::with_output(Box::new(io::sink())).interpret(&script);
+        assert_eq!(vm_error.unwrap_err().code, code, "{}", source);
+        code
+    }
+
+    #[test]
+    fn test_errors_carry_their_codes() {
+        let arguments = vec!["1"; 256].join(", ");
+        let locals: String = (0..256).map(|i| format!("var a{};", i)).collect();
+        let outer: String = (0..200).map(|i| format!("var x{};", i)).collect();
+        let inner: String = (0..200).map(|i| format!("var y{};", i)).collect();
+        let captured: String = (0..200).map(|i| format!("x{}; y{};", i, i)).collect();
+        let long_body = "nil;".repeat(40_000);
+        let constants: String = (0..=65_536).map(|i| format!("{};", i)).collect();
+
+        let cases = [
+            ("var a = 1 $ 2;".to_string(), codes::UNEXPECTED_CHARACTER),
+            ("print \"hello;".to_string(), codes::UNTERMINATED_STRING),
+            ("print (1 + 2;".to_string(), codes::UNCLOSED_PARENTHESIS),
+            ("print ;".to_string(), codes::EXPECTED_EXPRESSION),
+            ("print 1".to_string(), codes::MISSING_SEMICOLON),
+            ("if true) print 1;".to_string(), codes::MISSING_DELIMITER),
+            ("var 1 = 2;".to_string(), codes::EXPECTED_NAME),
+            ("1 = 2;".to_string(), codes::INVALID_ASSIGNMENT_TARGET),
+            (
+                format!("print clock({});", arguments),
+                codes::TOO_MANY_ARGUMENTS,
+            ),
+            ("{ var a = a; }".to_string(), codes::READ_IN_OWN_INITIALIZER),
+            ("{ var a; var a; }".to_string(), codes::ALREADY_DECLARED),
+            ("return 1;".to_string(), codes::TOP_LEVEL_RETURN),
+            (
+                "class A { init() { return 1; } }".to_string(),
+                codes::RETURN_FROM_INITIALIZER,
+            ),
+            ("print this;".to_string(), codes::THIS_OUTSIDE_CLASS),
+            (
+                "class A { f() { super.f(); } }".to_string(),
+                codes::INVALID_SUPER,
+            ),
+            ("class A < A {}".to_string(), codes::INHERITS_FROM_ITSELF),
+            ("print -\"a\";".to_string(), codes::OPERAND_NOT_A_NUMBER),
+            ("print 1 + nil;".to_string(), codes::INVALID_PLUS_OPERANDS),
+            ("print a;".to_string(), codes::UNDEFINED_VARIABLE),
+            (
+                "class A {} print A().x;".to_string(),
+                codes::UNDEFINED_PROPERTY,
+            ),
+            ("var a = 1; a.x = 2;".to_string(), codes::NOT_AN_INSTANCE),
+            ("\"a\"();".to_string(), codes::NOT_CALLABLE),
+            ("fun f(a) {} f();".to_string(), codes::WRONG_ARGUMENT_COUNT),
+            (
+                "var A = 1; class B < A {}".to_string(),
+                codes::SUPERCLASS_NOT_A_CLASS,
+            ),
+            ("fun f() { f(); } f();".to_string(), codes::STACK_OVERFLOW),
+            (format!("fun f() {{ {} }}", locals), codes::TOO_MANY_LOCALS),
+            (
+                format!(
+                    "fun a() {{ {} fun b() {{ {} fun c() {{ {} }} }} }}",
+                    outer, inner, captured
+                ),
+                codes::TOO_MANY_UPVALUES,
+            ),
+            (
+                format!("if (true) {{ {} }}", long_body),
+                codes::JUMP_TOO_LARGE,
+            ),
+            (constants, codes::TOO_MANY_CONSTANTS),
+            ("print \"\\q\";".to_string(), codes::INVALID_ESCAPE),
+            (
+                "print \"a ${b".to_string(),
+                codes::UNTERMINATED_INTERPOLATION,
+            ),
+            ("/* a".to_string(), codes::UNTERMINATED_BLOCK_COMMENT),
+        ];
+
+        // the tree-walker's default depth assumes a main thread's stack
+        let main_sized = std::thread::Builder::new().stack_size(8 << 20);
+        main_sized
+            .spawn(move || {
+                for (source, code) in &cases {
+                    let prefix: String = source.chars().take(40).collect();
+                    assert_eq!(first_code(source), Some(*code), "{}", prefix);
+                }
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_native_errors_have_no_code() {
+        let mut interpreter = Interpreter::with_output(Box::new(io::sink()));
+        interpreter.define_native("check", 1, |_, arguments| match arguments[0] {
+            Value::Number(number) => Ok(Value::Number(number)),
+            _ => Err("Expected a number.".to_string()),
+        });
+
+        let statements = Parser::new(Scanner::new("check(nil);")).parse();
+        let error = interpreter.interpret(&statements).unwrap_err();
+        assert_eq!(error.message, "Expected a number.");
+        assert_eq!(error.code, None);
+        assert_eq!(error.to_diagnostic().code, None);
+    }
+
+    #[test]
+    fn test_compiled_scripts_report_lines_without_columns() {
+        let source = "var a = 1;\n\nprint a + \"b\";";
+        let statements = Parser::new(Scanner::new(source)).parse();
+        let data = serialize(&Compiler::new(source).compile(&statements));
+        let script = deserialize(&data).unwrap();
+
+        let error = Vm::with_output(Box::new(io::sink()))
+            .interpret(&script)
+            .unwrap_err();
+        let json = to_json(&error.to_diagnostic(), "main.loxc", None);
+        assert!(
+            json.starts_with("{\"file\":\"main.loxc\",\"line\":3,\"column\":null,"),
+            "{}",
+            json
+        );
+    }
+}
@@ -0,0 +1,268 @@
+use std::fmt::Write;
+
+use super::{Diagnostic, Severity};
+use crate::domain::Span;
+
+/*
+    Renders a Diagnostic the way rustc does:
+
+        error: Expect expression.
+         --> main.lox:1:5
+          |
+        1 | (72 +)
+          |      ^ found ')'
+
+    Lines and columns are worked out from the byte offsets of the spans, so
+    the renderer only needs the source the spans point into.
+*/
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const CYAN: &str = "\x1b[1;36m";
+
+// 1-based line and column (in characters) of a byte offset into `source`
+pub fn location(source: &str, offset: usize) -> (usize, usize) {
+    let offset = floor_char_boundary(source, offset);
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    let column = source[line_start..offset].chars().count() + 1;
+    (line, column)
+}
+
+fn floor_char_boundary(source: &str, offset: usize) -> usize {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+// the byte range of the line holding `offset`, without its newline
+fn line_bounds(source: &str, offset: usize) -> (usize, usize) {
+    let offset = floor_char_boundary(source, offset);
+    let start = source[..offset].rfind('\n').map_or(0, |index| index + 1);
+    let end = source[offset..]
+        .find('\n')
+        .map_or(source.len(), |index| offset + index);
+    (start, end)
+}
+
+struct Style {
+    color: bool,
+}
+
+impl Style {
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", code, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+pub fn render(diagnostic: &Diagnostic, filename: &str, source: &str, color: bool) -> String {
+    let style = Style { color };
+    let mut out = String::new();
+
+    let severity_color = match diagnostic.severity {
+        Severity::Error => RED,
+        Severity::Warning => YELLOW,
+        Severity::Note => CYAN,
+    };
+    let heading = match &diagnostic.code {
+        Some(code) => format!("{}[{}]", diagnostic.severity, code),
+        None => diagnostic.severity.to_string(),
+    };
+    let _ = writeln!(
+        out,
+        "{}{}",
+        style.paint(severity_color, &heading),
+        style.paint(BOLD, &format!(": {}", diagnostic.message))
+    );
+
+    // the primary span comes first, then any label that points somewhere else
+    let primary_message = diagnostic
+        .labels
+        .iter()
+        .find(|label| label.span == diagnostic.span)
+        .map(|label| label.message.as_str());
+    let mut snippets = vec![(diagnostic.span, primary_message, true)];
+    for label in &diagnostic.labels {
+        if label.span != diagnostic.span {
+            snippets.push((label.span, Some(label.message.as_str()), false));
+        }
+    }
+
+    let gutter_width = snippets
+        .iter()
+        .map(|(span, _, _)| location(source, span.start).0.to_string().len())
+        .max()
+        .unwrap_or(1);
+    let padding = " ".repeat(gutter_width);
+
+    let (line, column) = location(source, diagnostic.span.start);
+    let _ = writeln!(
+        out,
+        "{}{} {}:{}:{}",
+        padding,
+        style.paint(BLUE, "-->"),
+        filename,
+        line,
+        column
+    );
+    let _ = writeln!(out, "{} {}", padding, style.paint(BLUE, "|"));
+
+    for (span, message, primary) in snippets {
+        render_snippet(
+            &mut out,
+            &style,
+            source,
+            span,
+            message,
+            primary,
+            gutter_width,
+            severity_color,
+        );
+    }
+
+    for note in &diagnostic.notes {
+        let _ = writeln!(
+            out,
+            "{} {} {}",
+            padding,
+            style.paint(BLUE, "="),
+            style.paint(BOLD, &format!("note: {}", note))
+        );
+    }
+
+    if let Some(code) = &diagnostic.code {
+        let _ = writeln!(
+            out,
+            "{} {} {}",
+            padding,
+            style.paint(BLUE, "="),
+            style.paint(
+                BOLD,
+                &format!("help: run `explain {}` for more information", code)
+            )
+        );
+    }
+
+    out
+}
+
+#[allow(clippy::too_many_arguments)]
+fn render_snippet(
+    out: &mut String,
+    style: &Style,
+    source: &str,
+    span: Span,
+    message: Option<&str>,
+    primary: bool,
+    gutter_width: usize,
+    severity_color: &str,
+) {
+    let (line, _) = location(source, span.start);
+    let (line_start, line_end) = line_bounds(source, span.start);
+    let text = &source[line_start..line_end];
+
+    // spans running past the end of the line are underlined up to it
+    let end = floor_char_boundary(source, span.end.min(line_end).max(span.start));
+    let start = floor_char_boundary(source, span.start);
+    let width = source[start..end].chars().count().max(1);
+
+    // tabs before the span are kept so the markers line up with the text
+    // however wide the terminal draws them, as rustc does
+    let padding: String = source[line_start..start]
+        .chars()
+        .map(|character| if character == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    let (marker, marker_color) = if primary {
+        ("^", severity_color)
+    } else {
+        ("-", BLUE)
+    };
+    let mut underline = marker.repeat(width);
+    if let Some(message) = message {
+        underline.push(' ');
+        underline.push_str(message);
+    }
+
+    let _ = writeln!(
+        out,
+        "{} {} {}",
+        style.paint(BLUE, &format!("{:>width$}", line, width = gutter_width)),
+        style.paint(BLUE, "|"),
+        text
+    );
+    let _ = writeln!(
+        out,
+        "{} {} {}{}",
+        " ".repeat(gutter_width),
+        style.paint(BLUE, "|"),
+        padding,
+        style.paint(marker_color, &underline)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_location() {
+        let source = "var a;\nprint é + b;";
+        assert_eq!(location(source, 0), (1, 1));
+        assert_eq!(location(source, 7), (2, 1));
+        // columns count characters, not bytes
+        assert_eq!(location(source, source.find('+').unwrap()), (2, 9));
+    }
+
+    #[test]
+    fn test_render_underlines_the_span() {
+        let source = "var a = 1;\nprint a + \"x\";\n";
+        let start = source.find("\"x\"").unwrap();
+        let diagnostic = Diagnostic::error("Bad operand.".to_string(), Span::new(start, start + 3))
+            .with_label(Span::new(start, start + 3), "a string".to_string())
+            .with_note("only numbers can be added here".to_string());
+
+        assert_eq!(
+            render(&diagnostic, "test.lox", source, false),
+            "error: Bad operand.\n \
+             --> test.lox:2:11\n  \
+             |\n\
+             2 | print a + \"x\";\n  \
+             |           ^^^ a string\n  \
+             = note: only numbers can be added here\n"
+        );
+    }
+
+    #[test]
+    fn test_render_empty_span_at_end_of_input() {
+        let source = "print (1";
+        let diagnostic =
+            Diagnostic::error("Expect ')' after expression.".to_string(), Span::new(8, 8));
+
+        let rendered = render(&diagnostic, "test.lox", source, false);
+        assert!(rendered.contains("1 | print (1\n  |         ^\n"));
+    }
+
+    #[test]
+    fn test_render_keeps_tabs_before_the_span() {
+        let source = "{\n\t\tprint x;\n}";
+        let start = source.find('x').unwrap();
+        let diagnostic = Diagnostic::error(
+            "Undefined variable 'x'.".to_string(),
+            Span::new(start, start + 1),
+        );
+
+        let rendered = render(&diagnostic, "test.lox", source, false);
+        assert!(rendered.contains("2 | \t\tprint x;\n  | \t\t      ^\n"));
+    }
+}
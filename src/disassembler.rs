@@ -0,0 +1,184 @@
+use std::fmt::Write;
+
+use crate::chunk::{Chunk, Constant, Function, OpCode};
+
+/*
+    Prints compiled bytecode in a readable form, one instruction per line:
+
+        == <script> ==
+        0000    1 OP_CONSTANT         0 '1.0'
+        0003    | OP_PRINT
+
+    Each line shows the offset of the instruction, its source line (`|` when
+    it is the same as the previous instruction's), the opcode and its operands,
+    with constants resolved. Functions nested in a chunk are printed after it.
+
+    Reference - https://craftinginterpreters.com/chunks-of-bytecode.html#disassembling-chunks
+*/
+
+pub fn disassemble(function: &Function) -> String {
+    let mut out = String::new();
+    disassemble_function(function, &mut out);
+    out
+}
+
+fn disassemble_function(function: &Function, out: &mut String) {
+    let _ = writeln!(out, "== {} ==", function);
+    let chunk = &function.chunk;
+
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        offset = disassemble_instruction(chunk, offset, out);
+    }
+
+    for constant in &chunk.constants {
+        if let Constant::Function(nested) = constant {
+            out.push('\n');
+            disassemble_function(nested, out);
+        }
+    }
+}
+
+// writes the instruction at `offset` and returns the offset of the next one
+pub fn disassemble_instruction(chunk: &Chunk, offset: usize, out: &mut String) -> usize {
+    let _ = write!(out, "{:04} ", offset);
+    let line = chunk.line_at(offset);
+    if offset > 0 && line == chunk.line_at(offset - 1) {
+        out.push_str("   | ");
+    } else {
+        let _ = write!(out, "{:4} ", line);
+    }
+
+    let byte = chunk.code[offset];
+    let Ok(op) = OpCode::try_from(byte) else {
+        let _ = writeln!(out, "Unknown opcode {}", byte);
+        return offset + 1;
+    };
+
+    match op {
+        OpCode::Constant
+        | OpCode::GetGlobal
+        | OpCode::DefineGlobal
+        | OpCode::SetGlobal
+        | OpCode::GetProperty
+        | OpCode::SetProperty
+        | OpCode::GetSuper
+        | OpCode::Class
+        | OpCode::Method => constant_instruction(op, chunk, offset, out),
+        OpCode::GetLocal
+        | OpCode::SetLocal
+        | OpCode::GetUpvalue
+        | OpCode::SetUpvalue
+        | OpCode::Call => byte_instruction(op, chunk, offset, out),
+        OpCode::Jump | OpCode::JumpIfFalse => jump_instruction(op, 1, chunk, offset, out),
+        OpCode::Loop => jump_instruction(op, -1, chunk, offset, out),
+        OpCode::Closure => closure_instruction(chunk, offset, out),
+        _ => {
+            let _ = writeln!(out, "{}", op);
+            offset + 1
+        }
+    }
+}
+
+fn constant_instruction(op: OpCode, chunk: &Chunk, offset: usize, out: &mut String) -> usize {
+    let index = chunk.read_u16(offset + 1) as usize;
+    let _ = writeln!(out, "{:<16} {:4} '{}'", op, index, chunk.constants[index]);
+    offset + 3
+}
+
+fn byte_instruction(op: OpCode, chunk: &Chunk, offset: usize, out: &mut String) -> usize {
+    let _ = writeln!(out, "{:<16} {:4}", op, chunk.code[offset + 1]);
+    offset + 2
+}
+
+fn jump_instruction(
+    op: OpCode,
+    sign: isize,
+    chunk: &Chunk,
+    offset: usize,
+    out: &mut String,
+) -> usize {
+    let jump = chunk.read_u16(offset + 1) as isize;
+    let target = offset as isize + 3 + sign * jump;
+    let _ = writeln!(out, "{:<16} {:4} -> {}", op, offset, target);
+    offset + 3
+}
+
+fn closure_instruction(chunk: &Chunk, offset: usize, out: &mut String) -> usize {
+    let index = chunk.read_u16(offset + 1) as usize;
+    let constant = &chunk.constants[index];
+    let _ = writeln!(out, "{:<16} {:4} {}", OpCode::Closure, index, constant);
+
+    let mut offset = offset + 3;
+    if let Constant::Function(function) = constant {
+        for _ in 0..function.upvalue_count {
+            let kind = if chunk.code[offset] == 1 {
+                "local"
+            } else {
+                "upvalue"
+            };
+            let _ = writeln!(
+                out,
+                "{:04}    |                     {} {}",
+                offset,
+                kind,
+                chunk.code[offset + 1]
+            );
+            offset += 2;
+        }
+    }
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn compile(source: &str) -> Function {
+        let statements = Parser::new(Scanner::new(source)).parse();
+        let function = Compiler::new(source).compile(&statements);
+        Function::clone(&function)
+    }
+
+    #[test]
+    fn test_disassemble_nested_function() {
+        let source = "var a = 1;\nfun f(b) {\n  return a + b;\n}\nprint f(2);\n";
+        assert_eq!(
+            disassemble(&compile(source)),
+            "\
+== <script> ==
+0000    1 OP_CONSTANT         1 '1.0'
+0003    | OP_DEFINE_GLOBAL    0 'a'
+0006    2 OP_CLOSURE          3 <fn f>
+0009    | OP_DEFINE_GLOBAL    2 'f'
+0012    5 OP_GET_GLOBAL       2 'f'
+0015    | OP_CONSTANT         4 '2.0'
+0018    | OP_CALL             1
+0020    | OP_PRINT
+0021    6 OP_NIL
+0022    | OP_RETURN
+
+== <fn f> ==
+0000    3 OP_GET_GLOBAL       0 'a'
+0003    | OP_GET_LOCAL        1
+0005    | OP_ADD
+0006    | OP_RETURN
+0007    4 OP_NIL
+0008    | OP_RETURN
+"
+        );
+    }
+
+    #[test]
+    fn test_disassemble_jumps_and_upvalues() {
+        let source = "fun f() { var x; fun g() { return x; } while (x) x = nil; }";
+        let output = disassemble(&compile(source));
+        assert!(output
+            .contains("OP_CLOSURE          0 <fn g>\n0004    |                     local 1\n"));
+        assert!(output.contains("0008    | OP_JUMP_IF_FALSE    8 -> 19\n"));
+        assert!(output.contains("0016    | OP_LOOP            16 -> 6\n"));
+    }
+}
@@ -1,79 +1,316 @@
-use super::{token::Token, Literal};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::{token::Token, Literal, Span};
 
 /*
 
 The Expression struct should have the following methods
 
-expression     → equality ;
+expression     → assignment ;
+assignment     → ( call "." )? IDENTIFIER "=" assignment
+               | logic_or ;
+logic_or       → logic_and ( "or" logic_and )* ;
+logic_and      → equality ( "and" equality )* ;
 equality       → comparison ( ( "!=" | "==" ) comparison )* ;
 comparison     → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
 term           → factor ( ( "-" | "+" ) factor )* ;
 factor         → unary ( ( "/" | "*" ) unary )* ;
 unary          → ( "!" | "-" ) unary
-               | primary ;
+               | call ;
+call           → primary ( "(" arguments? ")" | "." IDENTIFIER )* ;
+arguments      → expression ( "," expression )* ;
 primary        → NUMBER | STRING | "true" | "false" | "nil"
-               | "(" expression ")" ;
+               | "(" expression ")" | IDENTIFIER | "this"
+               | "super" "." IDENTIFIER ;
 
 */
 
+// Variable, Assign, This and Super nodes carry an id so the resolver can record how far
+// away their binding lives without needing the node's address.
+//
+// Every node carries the span of source it was parsed from. The constructors
+// derive it from the children and tokens, only literals and groupings,
+// whose delimiting tokens are not kept, take it explicitly.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+fn next_id() -> usize {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
 #[derive(Debug, Clone)]
 pub enum Expression {
+    Assign {
+        id: usize,
+        name: Token,
+        value: Box<Expression>,
+        span: Span,
+    },
     Binary {
         left: Box<Expression>,
         operator: Token,
         right: Box<Expression>,
+        span: Span,
+    },
+    Call {
+        callee: Box<Expression>,
+        paren: Token,
+        arguments: Vec<Expression>,
+        span: Span,
+    },
+    Get {
+        object: Box<Expression>,
+        name: Token,
+        span: Span,
+    },
+    Grouping {
+        expression: Box<Expression>,
+        span: Span,
+    },
+    // `"a ${b} c"`, the string segments are literals between the expressions
+    Interpolation {
+        parts: Vec<Expression>,
+        span: Span,
+    },
+    Literal {
+        value: Literal,
+        span: Span,
+    },
+    Logical {
+        left: Box<Expression>,
+        operator: Token,
+        right: Box<Expression>,
+        span: Span,
+    },
+    Set {
+        object: Box<Expression>,
+        name: Token,
+        value: Box<Expression>,
+        span: Span,
+    },
+    Super {
+        id: usize,
+        keyword: Token,
+        method: Token,
+        span: Span,
+    },
+    This {
+        id: usize,
+        keyword: Token,
+        span: Span,
     },
-    Grouping(Box<Expression>),
     Unary {
         operator: Token,
         right: Box<Expression>,
+        span: Span,
+    },
+    Variable {
+        id: usize,
+        name: Token,
+        span: Span,
     },
-    Literal(Literal),
 }
 
 impl Expression {
+    pub fn new_assign(name: Token, value: Box<Expression>) -> Self {
+        Self::Assign {
+            id: next_id(),
+            span: name.span.to(value.span()),
+            name,
+            value,
+        }
+    }
+
     pub fn new_binary(left: Box<Expression>, operator: Token, right: Box<Expression>) -> Self {
         Self::Binary {
+            span: left.span().to(right.span()),
+            left,
+            operator,
+            right,
+        }
+    }
+
+    pub fn new_call(callee: Box<Expression>, paren: Token, arguments: Vec<Expression>) -> Self {
+        Self::Call {
+            span: callee.span().to(paren.span),
+            callee,
+            paren,
+            arguments,
+        }
+    }
+
+    pub fn new_get(object: Box<Expression>, name: Token) -> Self {
+        Self::Get {
+            span: object.span().to(name.span),
+            object,
+            name,
+        }
+    }
+
+    // the span includes the parentheses
+    pub fn new_grouping(expression: Expression, span: Span) -> Self {
+        Self::Grouping {
+            expression: Box::new(expression),
+            span,
+        }
+    }
+
+    // the span covers the whole string, quotes included
+    pub fn new_interpolation(parts: Vec<Expression>, span: Span) -> Self {
+        Self::Interpolation { parts, span }
+    }
+
+    pub fn new_literal(literal: Literal, span: Span) -> Self {
+        Self::Literal {
+            value: literal,
+            span,
+        }
+    }
+
+    pub fn new_logical(left: Box<Expression>, operator: Token, right: Box<Expression>) -> Self {
+        Self::Logical {
+            span: left.span().to(right.span()),
             left,
             operator,
             right,
         }
     }
 
-    pub fn new_grouping(expression: Expression) -> Self {
-        Self::Grouping(Box::new(expression))
+    pub fn new_set(object: Box<Expression>, name: Token, value: Box<Expression>) -> Self {
+        Self::Set {
+            span: object.span().to(value.span()),
+            object,
+            name,
+            value,
+        }
+    }
+
+    pub fn new_super(keyword: Token, method: Token) -> Self {
+        Self::Super {
+            id: next_id(),
+            span: keyword.span.to(method.span),
+            keyword,
+            method,
+        }
+    }
+
+    pub fn new_this(keyword: Token) -> Self {
+        Self::This {
+            id: next_id(),
+            span: keyword.span,
+            keyword,
+        }
     }
 
     pub fn new_unary(operator: Token, right: Box<Expression>) -> Self {
-        Self::Unary { operator, right }
+        Self::Unary {
+            span: operator.span.to(right.span()),
+            operator,
+            right,
+        }
+    }
+
+    pub fn new_variable(name: Token) -> Self {
+        Self::Variable {
+            id: next_id(),
+            span: name.span,
+            name,
+        }
     }
 
-    pub fn new_literal(literal: Literal) -> Self {
-        Self::Literal(literal)
+    pub fn span(&self) -> Span {
+        match self {
+            Expression::Assign { span, .. }
+            | Expression::Binary { span, .. }
+            | Expression::Call { span, .. }
+            | Expression::Get { span, .. }
+            | Expression::Grouping { span, .. }
+            | Expression::Interpolation { span, .. }
+            | Expression::Literal { span, .. }
+            | Expression::Logical { span, .. }
+            | Expression::Set { span, .. }
+            | Expression::Super { span, .. }
+            | Expression::This { span, .. }
+            | Expression::Unary { span, .. }
+            | Expression::Variable { span, .. } => *span,
+        }
     }
 }
 
 impl std::fmt::Display for Expression {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Expression::Assign { name, value, .. } => {
+                write!(f, "(= {} {})", name.lexeme, value)
+            }
             Expression::Binary {
                 left,
                 operator,
                 right,
+                ..
+            } => {
+                write!(f, "({} {} {})", operator.lexeme, left, right)
+            }
+            Expression::Call {
+                callee, arguments, ..
+            } => {
+                write!(f, "(call {}", callee)?;
+                for argument in arguments {
+                    write!(f, " {}", argument)?;
+                }
+                write!(f, ")")
+            }
+            Expression::Logical {
+                left,
+                operator,
+                right,
+                ..
             } => {
                 write!(f, "({} {} {})", operator.lexeme, left, right)
             }
-            Expression::Unary { operator, right } => {
+            Expression::Get { object, name, .. } => {
+                write!(f, "(. {} {})", object, name.lexeme)
+            }
+            Expression::Set {
+                object,
+                name,
+                value,
+                ..
+            } => {
+                write!(f, "(= (. {} {}) {})", object, name.lexeme, value)
+            }
+            Expression::Super { method, .. } => {
+                write!(f, "(super {})", method.lexeme)
+            }
+            Expression::This { keyword, .. } => {
+                write!(f, "{}", keyword.lexeme)
+            }
+            Expression::Unary {
+                operator, right, ..
+            } => {
                 write!(f, "({} {})", operator.lexeme, right)
             }
-            Expression::Literal(literal) => {
-                write!(f, "{}", literal)
+            Expression::Literal { value, .. } => {
+                write!(f, "{}", value)
+            }
+            Expression::Grouping { expression, .. } => {
+                write!(f, "(group {})", expression)
             }
-            Expression::Grouping(literal) => {
-                write!(f, "(group {})", literal)
+            Expression::Interpolation { parts, .. } => {
+                // segments are quoted so their spaces stay visible
+                write!(f, "(interpolate")?;
+                for part in parts {
+                    match part {
+                        Expression::Literal {
+                            value: Literal::String(segment),
+                            ..
+                        } => write!(f, " {:?}", segment)?,
+                        _ => write!(f, " {}", part)?,
+                    }
+                }
+                write!(f, ")")
             }
-            _ => {
-                write!(f, "null")
+            Expression::Variable { name, .. } => {
+                write!(f, "{}", name.lexeme)
             }
         }
     }
@@ -1,7 +1,9 @@
+use super::Symbol;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
-    Identifier(String),
-    String(String),
+    Identifier(Symbol),
+    String(Symbol),
     Number(f64),
     Boolean(bool),
     Nil,
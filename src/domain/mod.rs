@@ -1,3 +1,9 @@
+pub mod span;
+pub use span::Span;
+
+pub mod symbol;
+pub use symbol::Symbol;
+
 pub mod token;
 pub use token::Token;
 
@@ -9,3 +15,6 @@ pub use literal::Literal;
 
 pub mod expression;
 pub use expression::Expression;
+
+pub mod statement;
+pub use statement::{FunctionDeclaration, Stmt};
@@ -0,0 +1,177 @@
+use std::rc::Rc;
+
+use super::{token::Token, Expression, Span};
+
+/*
+
+program        → declaration* EOF ;
+declaration    → classDecl
+               | funDecl
+               | varDecl
+               | statement ;
+classDecl      → "class" IDENTIFIER ( "<" IDENTIFIER )?
+                 "{" function* "}" ;
+funDecl        → "fun" function ;
+function       → IDENTIFIER "(" parameters? ")" block ;
+parameters     → IDENTIFIER ( "," IDENTIFIER )* ;
+varDecl        → "var" IDENTIFIER ( "=" expression )? ";" ;
+statement      → exprStmt
+               | forStmt
+               | ifStmt
+               | printStmt
+               | returnStmt
+               | whileStmt
+               | block ;
+exprStmt       → expression ";" ;
+forStmt        → "for" "(" ( varDecl | exprStmt | ";" )
+                 expression? ";"
+                 expression? ")" statement ;
+ifStmt         → "if" "(" expression ")" statement
+               ( "else" statement )? ;
+printStmt      → "print" expression ";" ;
+returnStmt     → "return" expression? ";" ;
+whileStmt      → "while" "(" expression ")" statement ;
+block          → "{" declaration* "}" ;
+
+*/
+
+// shared between the tree and every function value created from it
+#[derive(Debug, Clone)]
+pub struct FunctionDeclaration {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
+    // from the name, or the `fun` keyword, to the closing brace
+    pub span: Span,
+}
+
+// Like expressions, every statement carries the span of source it covers,
+// from its first token to its last (usually the `;` or `}`).
+#[derive(Debug, Clone)]
+pub enum Stmt {
+    Block {
+        statements: Vec<Stmt>,
+        span: Span,
+    },
+    Class {
+        name: Token,
+        // always an `Expression::Variable`
+        superclass: Option<Expression>,
+        methods: Vec<Rc<FunctionDeclaration>>,
+        span: Span,
+    },
+    Expression {
+        expression: Expression,
+        span: Span,
+    },
+    Function(Rc<FunctionDeclaration>),
+    If {
+        condition: Expression,
+        then_branch: Box<Stmt>,
+        else_branch: Option<Box<Stmt>>,
+        span: Span,
+    },
+    Print {
+        expression: Expression,
+        span: Span,
+    },
+    Return {
+        keyword: Token,
+        value: Option<Expression>,
+        span: Span,
+    },
+    Var {
+        name: Token,
+        initializer: Option<Expression>,
+        span: Span,
+    },
+    While {
+        condition: Expression,
+        body: Box<Stmt>,
+        span: Span,
+    },
+}
+
+impl Stmt {
+    pub fn new_block(statements: Vec<Stmt>, span: Span) -> Self {
+        Self::Block { statements, span }
+    }
+
+    pub fn new_class(
+        name: Token,
+        superclass: Option<Expression>,
+        methods: Vec<Rc<FunctionDeclaration>>,
+        span: Span,
+    ) -> Self {
+        Self::Class {
+            name,
+            superclass,
+            methods,
+            span,
+        }
+    }
+
+    pub fn new_expression(expression: Expression, span: Span) -> Self {
+        Self::Expression { expression, span }
+    }
+
+    pub fn new_function(declaration: Rc<FunctionDeclaration>) -> Self {
+        Self::Function(declaration)
+    }
+
+    pub fn new_if(
+        condition: Expression,
+        then_branch: Box<Stmt>,
+        else_branch: Option<Box<Stmt>>,
+        span: Span,
+    ) -> Self {
+        Self::If {
+            condition,
+            then_branch,
+            else_branch,
+            span,
+        }
+    }
+
+    pub fn new_print(expression: Expression, span: Span) -> Self {
+        Self::Print { expression, span }
+    }
+
+    pub fn new_return(keyword: Token, value: Option<Expression>, span: Span) -> Self {
+        Self::Return {
+            keyword,
+            value,
+            span,
+        }
+    }
+
+    pub fn new_var(name: Token, initializer: Option<Expression>, span: Span) -> Self {
+        Self::Var {
+            name,
+            initializer,
+            span,
+        }
+    }
+
+    pub fn new_while(condition: Expression, body: Box<Stmt>, span: Span) -> Self {
+        Self::While {
+            condition,
+            body,
+            span,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Function(declaration) => declaration.span,
+            Stmt::Block { span, .. }
+            | Stmt::Class { span, .. }
+            | Stmt::Expression { span, .. }
+            | Stmt::If { span, .. }
+            | Stmt::Print { span, .. }
+            | Stmt::Return { span, .. }
+            | Stmt::Var { span, .. }
+            | Stmt::While { span, .. } => *span,
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::rc::Rc;
+
+/*
+    A Symbol is an interned string: equal strings share one allocation, so
+    cloning a Symbol is a reference count bump and comparing or hashing two
+    of them only looks at the pointer.
+
+    Lexemes, identifiers, string literals and runtime strings are all Symbols.
+
+    The interner only keeps strings alive while some Symbol still refers to
+    them, dropping the last one removes the string from the table.
+*/
+
+thread_local! {
+    static INTERNER: RefCell<HashSet<Rc<str>>> = RefCell::new(HashSet::new());
+}
+
+#[derive(Clone)]
+pub struct Symbol(Rc<str>);
+
+impl Symbol {
+    pub fn intern(string: &str) -> Self {
+        INTERNER.with(|interner| {
+            let mut interner = interner.borrow_mut();
+            if let Some(interned) = interner.get(string) {
+                return Symbol(Rc::clone(interned));
+            }
+
+            let interned: Rc<str> = Rc::from(string);
+            interner.insert(Rc::clone(&interned));
+            Symbol(interned)
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Drop for Symbol {
+    fn drop(&mut self) {
+        // the interner holds the other reference
+        if Rc::strong_count(&self.0) == 2 {
+            // the table may already be gone while the thread shuts down
+            let _ = INTERNER.try_with(|interner| {
+                if let Ok(mut interner) = interner.try_borrow_mut() {
+                    interner.remove(&*self.0);
+                }
+            });
+        }
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::ptr::hash(Rc::as_ptr(&self.0) as *const u8, state);
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(string: &str) -> Self {
+        Symbol::intern(string)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(string: String) -> Self {
+        Symbol::intern(&string)
+    }
+}
+
+impl std::fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl std::fmt::Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interned_count() -> usize {
+        INTERNER.with(|interner| interner.borrow().len())
+    }
+
+    #[test]
+    fn test_equal_strings_share_one_allocation() {
+        let first = Symbol::intern("shared");
+        let second = Symbol::from(String::from("shared"));
+        assert_eq!(first, second);
+        assert!(std::ptr::eq(first.as_str(), second.as_str()));
+        assert_ne!(first, Symbol::intern("other"));
+        assert_eq!(first, "shared");
+    }
+
+    #[test]
+    fn test_unused_strings_leave_the_table() {
+        let before = interned_count();
+        let symbol = Symbol::intern("short lived");
+        let copy = symbol.clone();
+        assert_eq!(interned_count(), before + 1);
+
+        drop(symbol);
+        assert_eq!(interned_count(), before + 1);
+        drop(copy);
+        assert_eq!(interned_count(), before);
+    }
+}
@@ -1,29 +1,40 @@
 use super::literal::Literal;
+use super::span::Span;
+use super::symbol::Symbol;
 use super::token_type::TokenType;
 
+// `line` is the line the token ends on, the one `[line N]` reports as jlox
+// does, `start_line` and `column` are the line and 1-based column where it
+// starts and `span` the bytes it covers in the source
 #[derive(Debug, Clone)]
 pub struct Token {
     pub token_type: TokenType,
-    pub lexeme: String,
+    pub lexeme: Symbol,
     pub literal: Option<Literal>,
     pub line: u32,
+    pub start_line: u32,
     pub column: u32,
+    pub span: Span,
 }
 
 impl Token {
     pub fn new(
         token_type: TokenType,
-        lexeme: String,
+        lexeme: Symbol,
         literal: Option<Literal>,
         line: u32,
+        start_line: u32,
         column: u32,
+        span: Span,
     ) -> Self {
         Self {
             token_type,
             lexeme,
             literal,
             line,
+            start_line,
             column,
+            span,
         }
     }
 }
@@ -32,14 +43,10 @@ impl std::fmt::Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if let Some(literal) = &self.literal {
             match literal {
-                Literal::Boolean(_) => {
-                    return write!(f, "{} {} null", self.token_type, self.lexeme)
-                }
-                Literal::Identifier(_) => {
-                    return write!(f, "{} {} null", self.token_type, self.lexeme)
-                }
-                Literal::Nil => return write!(f, "{} {} null", self.token_type, literal),
-                _ => return write!(f, "{} {} {}", self.token_type, self.lexeme, literal),
+                Literal::Boolean(_) => write!(f, "{} {} null", self.token_type, self.lexeme),
+                Literal::Identifier(_) => write!(f, "{} {} null", self.token_type, self.lexeme),
+                Literal::Nil => write!(f, "{} {} null", self.token_type, literal),
+                _ => write!(f, "{} {} {}", self.token_type, self.lexeme, literal),
             }
         } else {
             write!(f, "{} {} null", self.token_type, self.lexeme)
@@ -26,6 +26,9 @@ pub enum TokenType {
     // Literals.
     Identifier,
     String,
+    // the part of a string before a `${`, the rest comes after the `}` as
+    // another Interpolation or the closing String
+    Interpolation,
     Number,
     // Keywords.
     And,
@@ -71,6 +74,7 @@ impl fmt::Display for TokenType {
             TokenType::LessEqual => "LESS_EQUAL",
             TokenType::Identifier => "IDENTIFIER",
             TokenType::String => "STRING",
+            TokenType::Interpolation => "INTERPOLATION",
             TokenType::Number => "NUMBER",
             TokenType::And => "AND",
             TokenType::Class => "CLASS",
@@ -89,7 +93,6 @@ impl fmt::Display for TokenType {
             TokenType::Var => "VAR",
             TokenType::While => "WHILE",
             TokenType::Eof => "EOF",
-            _ => panic!("Unknown token type"),
         };
         write!(f, "{}", token_representation)
     }
@@ -0,0 +1,22 @@
+use super::{Interpreter, RuntimeError, Value};
+use crate::domain::token::Token;
+
+/*
+    Anything a Lox program can call: user functions, classes and natives.
+
+    Host code implements this trait (or uses `Interpreter::define_native`) to
+    expose Rust functions to scripts. The interpreter checks the number of
+    arguments against `arity` before `call` is invoked.
+*/
+
+pub trait Callable: std::fmt::Debug {
+    fn arity(&self) -> usize;
+
+    // `paren` is the closing paren of the call, errors should point at it
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError>;
+}
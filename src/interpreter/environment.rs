@@ -0,0 +1,125 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::{RuntimeError, Value};
+use crate::diagnostics::codes;
+use crate::domain::{token::Token, Symbol};
+
+/*
+    The Environment stores the bindings of variables to their values,
+    keyed by the identifier's interned lexeme.
+
+    Every block gets its own Environment pointing at the enclosing one, so
+    lookups walk outwards and inner declarations shadow outer ones.
+
+    Reference - https://craftinginterpreters.com/statements-and-state.html#environments
+*/
+
+#[derive(Debug, Clone, Default)]
+pub struct Environment {
+    values: HashMap<Symbol, Value>,
+    pub enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Self {
+            values: HashMap::new(),
+            enclosing: None,
+        }
+    }
+
+    pub fn new_enclosed(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Self {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    // redefining an existing variable is allowed and simply overwrites it
+    pub fn define(&mut self, name: Symbol, value: Value) {
+        self.values.insert(name, value);
+    }
+
+    pub fn get(&self, name: &Token) -> Result<Value, RuntimeError> {
+        if let Some(value) = self.values.get(&name.lexeme) {
+            return Ok(value.clone());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get(name),
+            None => Err(Self::undefined(name)),
+        }
+    }
+
+    // looks only at this environment's own bindings
+    pub fn lookup(&self, name: &Symbol) -> Option<Value> {
+        self.values.get(name).cloned()
+    }
+
+    // unlike define, assignment never creates a new variable
+    pub fn assign(&mut self, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        if let Some(slot) = self.values.get_mut(&name.lexeme) {
+            *slot = value;
+            return Ok(());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(Self::undefined(name)),
+        }
+    }
+
+    // reads from the environment `distance` hops outwards, as computed by the resolver
+    pub fn get_at(
+        environment: &Rc<RefCell<Environment>>,
+        distance: usize,
+        name: &Token,
+    ) -> Result<Value, RuntimeError> {
+        let ancestor = Self::ancestor(environment, distance);
+        let value = ancestor.borrow().lookup(&name.lexeme);
+        value.ok_or_else(|| Self::undefined(name))
+    }
+
+    pub fn assign_at(
+        environment: &Rc<RefCell<Environment>>,
+        distance: usize,
+        name: &Token,
+        value: Value,
+    ) -> Result<(), RuntimeError> {
+        let ancestor = Self::ancestor(environment, distance);
+        let mut ancestor = ancestor.borrow_mut();
+        match ancestor.values.get_mut(&name.lexeme) {
+            Some(slot) => {
+                *slot = value;
+                Ok(())
+            }
+            None => Err(Self::undefined(name)),
+        }
+    }
+
+    fn ancestor(
+        environment: &Rc<RefCell<Environment>>,
+        distance: usize,
+    ) -> Rc<RefCell<Environment>> {
+        let mut environment = Rc::clone(environment);
+        for _ in 0..distance {
+            let enclosing = environment
+                .borrow()
+                .enclosing
+                .clone()
+                .expect("resolver distance exceeds environment depth");
+            environment = enclosing;
+        }
+        environment
+    }
+
+    fn undefined(name: &Token) -> RuntimeError {
+        RuntimeError::new(
+            codes::UNDEFINED_VARIABLE,
+            format!("Undefined variable '{}'.", name.lexeme),
+            name.clone(),
+        )
+    }
+}
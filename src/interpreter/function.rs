@@ -0,0 +1,99 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::{Callable, Environment, Interpreter, LoxInstance, RuntimeError, Unwind, Value};
+use crate::diagnostics::codes;
+use crate::domain::{token::Token, FunctionDeclaration, Symbol};
+
+/*
+    A LoxFunction is the runtime value of a `fun` declaration.
+
+    It keeps the environment that was active when it was declared, so the body
+    sees the variables around the declaration, including later changes to them.
+
+    Methods are LoxFunctions too, binding one to an instance wraps its closure
+    in an environment that defines `this`.
+
+    Reference - https://craftinginterpreters.com/functions.html#local-functions-and-closures
+*/
+
+#[derive(Debug)]
+pub struct LoxFunction {
+    pub declaration: Rc<FunctionDeclaration>,
+    pub closure: Rc<RefCell<Environment>>,
+    pub is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn new(
+        declaration: Rc<FunctionDeclaration>,
+        closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> Self {
+        Self {
+            declaration,
+            closure,
+            is_initializer,
+        }
+    }
+
+    pub fn bind(&self, instance: Rc<RefCell<LoxInstance>>) -> LoxFunction {
+        let mut environment = Environment::new_enclosed(Rc::clone(&self.closure));
+        environment.define(Symbol::intern("this"), Value::Instance(instance));
+        LoxFunction::new(
+            Rc::clone(&self.declaration),
+            Rc::new(RefCell::new(environment)),
+            self.is_initializer,
+        )
+    }
+}
+
+impl Callable for LoxFunction {
+    fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        // report runaway recursion before the native stack runs out
+        if interpreter.call_depth >= interpreter.max_call_depth {
+            return Err(RuntimeError::new(
+                codes::STACK_OVERFLOW,
+                "Stack overflow.".to_string(),
+                paren.clone(),
+            ));
+        }
+
+        let mut environment = Environment::new_enclosed(Rc::clone(&self.closure));
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            environment.define(param.lexeme.clone(), argument);
+        }
+
+        interpreter.call_depth += 1;
+        let result =
+            interpreter.execute_block(&self.declaration.body, Rc::new(RefCell::new(environment)));
+        interpreter.call_depth -= 1;
+
+        match result {
+            // initializers always hand back the instance, even on a bare `return;`
+            Ok(()) | Err(Unwind::Return(_)) if self.is_initializer => Ok(self
+                .closure
+                .borrow()
+                .lookup(&Symbol::intern("this"))
+                .unwrap_or(Value::Nil)),
+            Ok(()) => Ok(Value::Nil),
+            Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::Error(error)) => Err(error),
+        }
+    }
+}
+
+impl std::fmt::Display for LoxFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn {}>", self.declaration.name.lexeme)
+    }
+}
@@ -0,0 +1,936 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::diagnostics::codes;
+use crate::domain::{token::Token, Expression, FunctionDeclaration, Stmt, Symbol, TokenType};
+
+pub mod callable;
+pub use callable::Callable;
+
+pub mod class;
+pub use class::{LoxClass, LoxInstance};
+
+pub mod environment;
+pub use environment::Environment;
+
+pub mod function;
+pub use function::LoxFunction;
+
+pub mod native;
+pub use native::NativeFunction;
+
+pub mod value;
+pub use value::Value;
+
+/*
+    The Interpreter walks the syntax tree produced by the Parser and computes
+    the value of each node.
+
+    Reference - https://craftinginterpreters.com/evaluating-expressions.html
+*/
+
+// the default limit on nested calls. A Lox call recurses on the native stack,
+// and this many calls of ordinary functions fit in the 8 MiB of a main thread
+// even in a debug build
+pub const MAX_CALL_DEPTH: usize = 256;
+
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    // errors raised by native functions have no code
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub token: Token,
+}
+
+impl RuntimeError {
+    pub fn new(code: &'static str, message: String, token: Token) -> Self {
+        Self {
+            code: Some(code),
+            message,
+            token,
+        }
+    }
+
+    pub fn uncoded(message: String, token: Token) -> Self {
+        Self {
+            code: None,
+            message,
+            token,
+        }
+    }
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}\n[line {}]", self.message, self.token.line)
+    }
+}
+
+// Statements stop executing either because of an error or because a
+// `return` is unwinding to the enclosing call.
+#[derive(Debug)]
+enum Unwind {
+    Error(RuntimeError),
+    Return(Value),
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Self {
+        Unwind::Error(error)
+    }
+}
+
+pub struct Interpreter {
+    pub globals: Rc<RefCell<Environment>>,
+    pub environment: Rc<RefCell<Environment>>,
+    // binding distances from the resolver, keyed by expression id
+    locals: HashMap<usize, usize>,
+    output: Box<dyn Write>,
+    // calls nested deeper than this are a stack overflow, hosts running the
+    // interpreter on a smaller or larger stack can change it
+    pub max_call_depth: usize,
+    // number of Lox functions currently being called
+    call_depth: usize,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self::with_output(Box::new(io::stdout()))
+    }
+
+    // `print` writes to the given output instead of stdout
+    pub fn with_output(output: Box<dyn Write>) -> Self {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        let mut interpreter = Self {
+            environment: Rc::clone(&globals),
+            globals,
+            locals: HashMap::new(),
+            output,
+            max_call_depth: MAX_CALL_DEPTH,
+            call_depth: 0,
+        };
+
+        interpreter.define_native("clock", 0, |_, _| {
+            let elapsed = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|error| error.to_string())?;
+            Ok(Value::Number(elapsed.as_secs_f64()))
+        });
+
+        interpreter
+    }
+
+    pub fn define_global(&mut self, name: &str, value: Value) {
+        self.globals
+            .borrow_mut()
+            .define(Symbol::intern(name), value);
+    }
+
+    // exposes a Rust closure to scripts as a global function
+    pub fn define_native<F>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: Fn(&mut Interpreter, Vec<Value>) -> Result<Value, String> + 'static,
+    {
+        let native = NativeFunction::new(name.to_string(), arity, function);
+        self.define_global(name, Value::Native(Rc::new(native)));
+    }
+
+    // takes the binding distances computed by the `Resolver`
+    pub fn resolve(&mut self, locals: HashMap<usize, usize>) {
+        self.locals.extend(locals);
+    }
+
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<(), RuntimeError> {
+        for statement in statements {
+            match self.execute(statement) {
+                Ok(()) => {}
+                Err(Unwind::Error(error)) => return Err(error),
+                // a top level return simply ends the program
+                Err(Unwind::Return(_)) => return Ok(()),
+            }
+        }
+        Ok(())
+    }
+
+    // like `evaluate`, the longer arms are methods of their own
+    fn execute(&mut self, statement: &Stmt) -> Result<(), Unwind> {
+        match statement {
+            Stmt::Block { statements, .. } => {
+                let environment = Environment::new_enclosed(Rc::clone(&self.environment));
+                self.execute_block(statements, Rc::new(RefCell::new(environment)))?;
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } => self.execute_class(name, superclass.as_ref(), methods)?,
+            Stmt::Expression { expression, .. } => {
+                self.evaluate(expression)?;
+            }
+            Stmt::Function(declaration) => self.execute_function(declaration),
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                if self.evaluate(condition)?.is_truthy() {
+                    self.execute(then_branch)?;
+                } else if let Some(else_branch) = else_branch {
+                    self.execute(else_branch)?;
+                }
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                while self.evaluate(condition)?.is_truthy() {
+                    self.execute(body)?;
+                }
+            }
+            Stmt::Print { expression, .. } => self.execute_print(expression)?,
+            Stmt::Return { value, .. } => {
+                let value = match value {
+                    Some(value) => self.evaluate(value)?,
+                    None => Value::Nil,
+                };
+                return Err(Unwind::Return(value));
+            }
+            Stmt::Var {
+                name, initializer, ..
+            } => self.execute_var(name, initializer.as_ref())?,
+        }
+        Ok(())
+    }
+
+    fn execute_function(&mut self, declaration: &Rc<FunctionDeclaration>) {
+        let function =
+            LoxFunction::new(Rc::clone(declaration), Rc::clone(&self.environment), false);
+        self.environment.borrow_mut().define(
+            declaration.name.lexeme.clone(),
+            Value::Function(Rc::new(function)),
+        );
+    }
+
+    fn execute_print(&mut self, expression: &Expression) -> Result<(), RuntimeError> {
+        let value = self.evaluate(expression)?;
+        // a closed stdout is not something a Lox program can recover from
+        writeln!(self.output, "{}", value).expect("Failed to write output");
+        Ok(())
+    }
+
+    fn execute_var(
+        &mut self,
+        name: &Token,
+        initializer: Option<&Expression>,
+    ) -> Result<(), RuntimeError> {
+        let value = match initializer {
+            Some(initializer) => self.evaluate(initializer)?,
+            None => Value::Nil,
+        };
+        self.environment
+            .borrow_mut()
+            .define(name.lexeme.clone(), value);
+        Ok(())
+    }
+
+    fn execute_class(
+        &mut self,
+        name: &Token,
+        superclass: Option<&Expression>,
+        methods: &[Rc<FunctionDeclaration>],
+    ) -> Result<(), RuntimeError> {
+        let superclass = match superclass {
+            Some(Expression::Variable {
+                id,
+                name: superclass_name,
+                ..
+            }) => match self.look_up_variable(*id, superclass_name)? {
+                Value::Class(class) => Some(class),
+                _ => {
+                    return Err(RuntimeError::new(
+                        codes::SUPERCLASS_NOT_A_CLASS,
+                        "Superclass must be a class.".to_string(),
+                        superclass_name.clone(),
+                    ))
+                }
+            },
+            _ => None,
+        };
+
+        self.environment
+            .borrow_mut()
+            .define(name.lexeme.clone(), Value::Nil);
+
+        // methods of a subclass close over an extra scope holding `super`
+        let enclosing = Rc::clone(&self.environment);
+        if let Some(superclass) = &superclass {
+            let mut environment = Environment::new_enclosed(Rc::clone(&enclosing));
+            environment.define(Symbol::intern("super"), Value::Class(Rc::clone(superclass)));
+            self.environment = Rc::new(RefCell::new(environment));
+        }
+
+        let methods = methods
+            .iter()
+            .map(|method| {
+                let is_initializer = method.name.lexeme == "init";
+                let function = LoxFunction::new(
+                    Rc::clone(method),
+                    Rc::clone(&self.environment),
+                    is_initializer,
+                );
+                (method.name.lexeme.clone(), Rc::new(function))
+            })
+            .collect();
+
+        self.environment = enclosing;
+
+        let class = LoxClass::new(name.lexeme.clone(), superclass, methods);
+        self.environment
+            .borrow_mut()
+            .assign(name, Value::Class(Rc::new(class)))
+    }
+
+    // runs the statements inside the given environment and always restores
+    // the previous one, even when a statement fails
+    fn execute_block(
+        &mut self,
+        statements: &[Stmt],
+        environment: Rc<RefCell<Environment>>,
+    ) -> Result<(), Unwind> {
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let mut result = Ok(());
+        for statement in statements {
+            result = self.execute(statement);
+            if result.is_err() {
+                break;
+            }
+        }
+        self.environment = previous;
+        result
+    }
+
+    // every arm with more than a few locals is its own method, so the frame
+    // that recursion through nested expressions and calls repeats stays small
+    pub fn evaluate(&mut self, expression: &Expression) -> Result<Value, RuntimeError> {
+        match expression {
+            Expression::Literal { value, .. } => Ok(Value::from(value)),
+            Expression::Grouping { expression, .. } => self.evaluate(expression),
+            Expression::Interpolation { parts, .. } => self.evaluate_interpolation(parts),
+            Expression::Call {
+                callee,
+                paren,
+                arguments,
+                ..
+            } => self.evaluate_call(callee, paren, arguments),
+            Expression::Variable { id, name, .. } => self.look_up_variable(*id, name),
+            Expression::This { id, keyword, .. } => self.look_up_variable(*id, keyword),
+            Expression::Super {
+                id,
+                keyword,
+                method,
+                ..
+            } => self.evaluate_super(*id, keyword, method),
+            Expression::Get { object, name, .. } => self.evaluate_get(object, name),
+            Expression::Set {
+                object,
+                name,
+                value,
+                ..
+            } => self.evaluate_set(object, name, value),
+            Expression::Assign {
+                id, name, value, ..
+            } => self.evaluate_assign(*id, name, value),
+            Expression::Logical {
+                left,
+                operator,
+                right,
+                ..
+            } => self.evaluate_logical(left, operator, right),
+            Expression::Unary {
+                operator, right, ..
+            } => {
+                let right = self.evaluate(right)?;
+                self.evaluate_unary(operator, right)
+            }
+            Expression::Binary {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+                self.evaluate_binary(left, operator, right)
+            }
+        }
+    }
+
+    // every part is shown the way `print` shows it
+    fn evaluate_interpolation(&mut self, parts: &[Expression]) -> Result<Value, RuntimeError> {
+        let mut string = String::new();
+        for part in parts {
+            string.push_str(&self.evaluate(part)?.to_string());
+        }
+        Ok(Value::String(Symbol::from(string)))
+    }
+
+    fn evaluate_call(
+        &mut self,
+        callee: &Expression,
+        paren: &Token,
+        arguments: &[Expression],
+    ) -> Result<Value, RuntimeError> {
+        let callee = self.evaluate(callee)?;
+
+        let mut values = Vec::with_capacity(arguments.len());
+        for argument in arguments {
+            values.push(self.evaluate(argument)?);
+        }
+
+        self.call(callee, paren, values)
+    }
+
+    fn evaluate_super(
+        &mut self,
+        id: usize,
+        keyword: &Token,
+        method: &Token,
+    ) -> Result<Value, RuntimeError> {
+        let distance = *self
+            .locals
+            .get(&id)
+            .expect("resolver always resolves 'super'");
+        let Value::Class(superclass) = Environment::get_at(&self.environment, distance, keyword)?
+        else {
+            unreachable!("'super' is always bound to a class");
+        };
+
+        // `this` is always one scope inside the one holding `super`
+        let this = Token::new(
+            TokenType::This,
+            Symbol::intern("this"),
+            None,
+            keyword.line,
+            keyword.start_line,
+            keyword.column,
+            keyword.span,
+        );
+        let Value::Instance(instance) =
+            Environment::get_at(&self.environment, distance - 1, &this)?
+        else {
+            unreachable!("'this' is always bound to an instance");
+        };
+
+        match superclass.find_method(&method.lexeme) {
+            Some(found) => Ok(Value::Function(Rc::new(found.bind(instance)))),
+            None => Err(RuntimeError::new(
+                codes::UNDEFINED_PROPERTY,
+                format!("Undefined property '{}'.", method.lexeme),
+                method.clone(),
+            )),
+        }
+    }
+
+    fn evaluate_get(&mut self, object: &Expression, name: &Token) -> Result<Value, RuntimeError> {
+        match self.evaluate(object)? {
+            Value::Instance(instance) => LoxInstance::get(&instance, name),
+            _ => Err(RuntimeError::new(
+                codes::NOT_AN_INSTANCE,
+                "Only instances have properties.".to_string(),
+                name.clone(),
+            )),
+        }
+    }
+
+    fn evaluate_set(
+        &mut self,
+        object: &Expression,
+        name: &Token,
+        value: &Expression,
+    ) -> Result<Value, RuntimeError> {
+        let Value::Instance(instance) = self.evaluate(object)? else {
+            return Err(RuntimeError::new(
+                codes::NOT_AN_INSTANCE,
+                "Only instances have fields.".to_string(),
+                name.clone(),
+            ));
+        };
+
+        let value = self.evaluate(value)?;
+        instance.borrow_mut().set(name, value.clone());
+        Ok(value)
+    }
+
+    fn evaluate_assign(
+        &mut self,
+        id: usize,
+        name: &Token,
+        value: &Expression,
+    ) -> Result<Value, RuntimeError> {
+        let value = self.evaluate(value)?;
+        match self.locals.get(&id) {
+            Some(distance) => {
+                Environment::assign_at(&self.environment, *distance, name, value.clone())?
+            }
+            None => self.globals.borrow_mut().assign(name, value.clone())?,
+        }
+        Ok(value)
+    }
+
+    // returns whichever operand decided the result, not a boolean
+    fn evaluate_logical(
+        &mut self,
+        left: &Expression,
+        operator: &Token,
+        right: &Expression,
+    ) -> Result<Value, RuntimeError> {
+        let left = self.evaluate(left)?;
+        let short_circuits = match operator.token_type {
+            TokenType::Or => left.is_truthy(),
+            _ => !left.is_truthy(),
+        };
+
+        if short_circuits {
+            Ok(left)
+        } else {
+            self.evaluate(right)
+        }
+    }
+
+    // unresolved variables are assumed to be globals
+    fn look_up_variable(&self, id: usize, name: &Token) -> Result<Value, RuntimeError> {
+        match self.locals.get(&id) {
+            Some(distance) => Environment::get_at(&self.environment, *distance, name),
+            None => self.globals.borrow().get(name),
+        }
+    }
+
+    // errors point at the closing paren of the call
+    fn call(
+        &mut self,
+        callee: Value,
+        paren: &Token,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        let callable: &dyn Callable = match &callee {
+            Value::Function(function) => function.as_ref(),
+            Value::Native(native) => native.as_ref(),
+            Value::Class(class) => class,
+            _ => {
+                return Err(RuntimeError::new(
+                    codes::NOT_CALLABLE,
+                    "Can only call functions and classes.".to_string(),
+                    paren.clone(),
+                ))
+            }
+        };
+
+        if arguments.len() != callable.arity() {
+            return Err(RuntimeError::new(
+                codes::WRONG_ARGUMENT_COUNT,
+                format!(
+                    "Expected {} arguments but got {}.",
+                    callable.arity(),
+                    arguments.len()
+                ),
+                paren.clone(),
+            ));
+        }
+
+        callable.call(self, paren, arguments)
+    }
+
+    fn evaluate_unary(&mut self, operator: &Token, right: Value) -> Result<Value, RuntimeError> {
+        match operator.token_type {
+            TokenType::Bang => Ok(Value::Boolean(!right.is_truthy())),
+            TokenType::Minus => {
+                let number = Self::number_operand(operator, &right)?;
+                Ok(Value::Number(-number))
+            }
+            _ => Err(RuntimeError::uncoded(
+                format!("Unknown unary operator '{}'.", operator.lexeme),
+                operator.clone(),
+            )),
+        }
+    }
+
+    fn evaluate_binary(
+        &mut self,
+        left: Value,
+        operator: &Token,
+        right: Value,
+    ) -> Result<Value, RuntimeError> {
+        match operator.token_type {
+            TokenType::EqualEqual => Ok(Value::Boolean(left == right)),
+            TokenType::BangEqual => Ok(Value::Boolean(left != right)),
+            TokenType::Plus => match (left, right) {
+                (Value::Number(left), Value::Number(right)) => Ok(Value::Number(left + right)),
+                (Value::String(left), Value::String(right)) => {
+                    Ok(Value::String(Symbol::from(format!("{}{}", left, right))))
+                }
+                _ => Err(RuntimeError::new(
+                    codes::INVALID_PLUS_OPERANDS,
+                    "Operands must be two numbers or two strings.".to_string(),
+                    operator.clone(),
+                )),
+            },
+            _ => {
+                let (left, right) = Self::number_operands(operator, &left, &right)?;
+                match operator.token_type {
+                    TokenType::Minus => Ok(Value::Number(left - right)),
+                    TokenType::Star => Ok(Value::Number(left * right)),
+                    TokenType::Slash => Ok(Value::Number(left / right)),
+                    TokenType::Greater => Ok(Value::Boolean(left > right)),
+                    TokenType::GreaterEqual => Ok(Value::Boolean(left >= right)),
+                    TokenType::Less => Ok(Value::Boolean(left < right)),
+                    TokenType::LessEqual => Ok(Value::Boolean(left <= right)),
+                    _ => Err(RuntimeError::uncoded(
+                        format!("Unknown binary operator '{}'.", operator.lexeme),
+                        operator.clone(),
+                    )),
+                }
+            }
+        }
+    }
+
+    // helpers to check operand types, errors point at the operator
+
+    fn number_operand(operator: &Token, operand: &Value) -> Result<f64, RuntimeError> {
+        match operand {
+            Value::Number(number) => Ok(*number),
+            _ => Err(RuntimeError::new(
+                codes::OPERAND_NOT_A_NUMBER,
+                "Operand must be a number.".to_string(),
+                operator.clone(),
+            )),
+        }
+    }
+
+    fn number_operands(
+        operator: &Token,
+        left: &Value,
+        right: &Value,
+    ) -> Result<(f64, f64), RuntimeError> {
+        Ok((
+            Self::number_operand(operator, left)?,
+            Self::number_operand(operator, right)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // collects everything the interpreter prints so tests can inspect it
+    #[derive(Clone, Default)]
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn evaluate(source: &str) -> Result<Value, RuntimeError> {
+        let mut parser = Parser::new(Scanner::new(source));
+        let expressions = parser.parse_expressions();
+        Interpreter::new().evaluate(&expressions[0])
+    }
+
+    fn run(source: &str) -> (String, Option<RuntimeError>) {
+        run_with_call_depth(source, MAX_CALL_DEPTH)
+    }
+
+    fn run_with_call_depth(source: &str, max_call_depth: usize) -> (String, Option<RuntimeError>) {
+        let mut parser = Parser::new(Scanner::new(source));
+        let statements = parser.parse();
+        assert!(parser.errors.is_empty(), "unexpected parse errors");
+
+        let mut resolver = Resolver::new();
+        resolver.resolve(&statements);
+        assert!(resolver.errors.is_empty(), "unexpected resolver errors");
+
+        let output = SharedOutput::default();
+        let mut interpreter = Interpreter::with_output(Box::new(output.clone()));
+        interpreter.max_call_depth = max_call_depth;
+        interpreter.resolve(resolver.locals);
+        let error = interpreter.interpret(&statements).err();
+        let printed = String::from_utf8(output.0.borrow().clone()).unwrap();
+        (printed, error)
+    }
+
+    #[test]
+    fn test_evaluate_arithmetic() {
+        assert_eq!(evaluate("(1 + 2) * 3 - 4 / 2").unwrap(), Value::Number(7.0));
+        assert_eq!(evaluate("-(3)").unwrap(), Value::Number(-3.0));
+    }
+
+    #[test]
+    fn test_evaluate_string_concatenation() {
+        assert_eq!(
+            evaluate("\"foo\" + \"bar\"").unwrap(),
+            Value::String(Symbol::intern("foobar"))
+        );
+    }
+
+    #[test]
+    fn test_evaluate_truthiness_and_equality() {
+        assert_eq!(evaluate("!nil").unwrap(), Value::Boolean(true));
+        assert_eq!(evaluate("!0").unwrap(), Value::Boolean(false));
+        assert_eq!(evaluate("1 == \"1\"").unwrap(), Value::Boolean(false));
+        assert_eq!(evaluate("nil == nil").unwrap(), Value::Boolean(true));
+    }
+
+    #[test]
+    fn test_evaluate_operand_errors() {
+        let error = evaluate("-\"a\"").unwrap_err();
+        assert_eq!(error.message, "Operand must be a number.");
+        assert_eq!(error.token.token_type, TokenType::Minus);
+
+        let error = evaluate("1 < \"x\"").unwrap_err();
+        assert_eq!(error.message, "Operand must be a number.");
+        assert_eq!(error.token.line, 1);
+    }
+
+    #[test]
+    fn test_run_print_and_globals() {
+        let (output, error) =
+            run("var a = 1; var b; print a + 2; print b; var a = \"x\"; print a;");
+        assert!(error.is_none());
+        assert_eq!(output, "3.0\nnil\nx\n");
+    }
+
+    #[test]
+    fn test_run_undefined_variable() {
+        let (output, error) = run("print 1; print missing;");
+        assert_eq!(output, "1.0\n");
+        assert_eq!(error.unwrap().message, "Undefined variable 'missing'.");
+    }
+
+    #[test]
+    fn test_run_block_scopes_and_assignment() {
+        let source = "var a = \"global\"; var b = 1;
+            { var a = \"inner\"; b = a = \"both\"; print a; }
+            print a; print b;";
+        let (output, error) = run(source);
+        assert!(error.is_none());
+        assert_eq!(output, "both\nglobal\nboth\n");
+    }
+
+    #[test]
+    fn test_run_block_restores_scope_after_error() {
+        let statements = Parser::new(Scanner::new("var a = 1; { var a = 2; -\"x\"; }")).parse();
+        let mut interpreter = Interpreter::with_output(Box::new(io::sink()));
+        assert!(interpreter.interpret(&statements).is_err());
+
+        let expressions = Parser::new(Scanner::new("a")).parse_expressions();
+        assert!(interpreter.environment.borrow().enclosing.is_none());
+        assert_eq!(
+            interpreter.evaluate(&expressions[0]).unwrap(),
+            Value::Number(1.0)
+        );
+    }
+
+    #[test]
+    fn test_run_assign_undefined_variable() {
+        let (_, error) = run("missing = 1;");
+        assert_eq!(error.unwrap().message, "Undefined variable 'missing'.");
+    }
+
+    #[test]
+    fn test_run_control_flow() {
+        let source = "
+            if (true) if (false) print 1; else print 2;
+            var i = 0;
+            while (i < 2) { print i; i = i + 1; }
+            for (var i = 10; i < 12; i = i + 1) print i;
+            print i;
+            print nil or \"default\";
+            print 0 and \"second\";
+            print false and missing;";
+        let (output, error) = run(source);
+        assert!(error.is_none());
+        assert_eq!(
+            output,
+            "2.0\n0.0\n1.0\n10.0\n11.0\n2.0\ndefault\nsecond\nfalse\n"
+        );
+    }
+
+    #[test]
+    fn test_run_functions_and_closures() {
+        let source = "
+            fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+            print fib(10);
+            fun makeCounter() {
+                var i = 0;
+                fun count() { i = i + 1; return i; }
+                return count;
+            }
+            var counter = makeCounter();
+            counter();
+            print counter();
+            var x = \"before\";
+            fun show() { print x; }
+            x = \"after\";
+            show();
+            print show;
+            fun nothing() {}
+            print nothing();";
+        let (output, error) = run(source);
+        assert!(error.is_none());
+        assert_eq!(output, "55.0\n2.0\nafter\n<fn show>\nnil\n");
+    }
+
+    #[test]
+    fn test_run_call_errors() {
+        let (_, error) = run("fun f(a, b) {}\nf(1\n);");
+        let error = error.unwrap();
+        assert_eq!(error.message, "Expected 2 arguments but got 1.");
+        assert_eq!(error.token.line, 3);
+
+        let (_, error) = run("\"not callable\"();");
+        assert_eq!(
+            error.unwrap().message,
+            "Can only call functions and classes."
+        );
+    }
+
+    #[test]
+    fn test_run_closure_binds_lexically() {
+        let source = "
+            var a = \"global\";
+            {
+                fun showA() { print a; }
+                showA();
+                var a = \"block\";
+                showA();
+            }";
+        let (output, error) = run(source);
+        assert!(error.is_none());
+        assert_eq!(output, "global\nglobal\n");
+    }
+
+    #[test]
+    fn test_run_classes() {
+        let source = "
+            class Point {
+                init(x, y) { this.x = x; this.y = y; }
+                sum() { return this.x + this.y; }
+            }
+            var p = Point(1, 2);
+            print p.sum();
+            var sum = p.sum;
+            p.x = 10;
+            print sum();
+            print Point;
+            print p;
+            print p.init(3, 4) == p;
+            print p.x;";
+        let (output, error) = run(source);
+        assert!(error.is_none());
+        assert_eq!(output, "3.0\n12.0\nPoint\nPoint instance\ntrue\n3.0\n");
+    }
+
+    #[test]
+    fn test_run_property_errors() {
+        let (_, error) = run("class A {} A().missing;");
+        assert_eq!(error.unwrap().message, "Undefined property 'missing'.");
+
+        let (_, error) = run("var a = 1; a.field = 2;");
+        assert_eq!(error.unwrap().message, "Only instances have fields.");
+    }
+
+    #[test]
+    fn test_run_inheritance() {
+        let source = "
+            class A {
+                init(name) { this.name = name; }
+                greet() { return \"A \" + this.name; }
+            }
+            class B < A {
+                greet() { return \"B then \" + super.greet(); }
+            }
+            var b = B(\"bob\");
+            print b.greet();
+            print b;";
+        let (output, error) = run(source);
+        assert!(error.is_none());
+        assert_eq!(output, "B then A bob\nB instance\n");
+
+        let (_, error) = run("var NotAClass = 1; class B < NotAClass {}");
+        assert_eq!(error.unwrap().message, "Superclass must be a class.");
+    }
+
+    #[test]
+    fn test_run_natives() {
+        let mut parser = Parser::new(Scanner::new(
+            "print clock() > 0; print clock; print double(21); print double(1, 2);",
+        ));
+        let statements = parser.parse();
+
+        let output = SharedOutput::default();
+        let mut interpreter = Interpreter::with_output(Box::new(output.clone()));
+        interpreter.define_native("double", 1, |_, arguments| match arguments[0] {
+            Value::Number(number) => Ok(Value::Number(number * 2.0)),
+            _ => Err("Argument must be a number.".to_string()),
+        });
+
+        let error = interpreter.interpret(&statements).unwrap_err();
+        assert_eq!(error.message, "Expected 1 arguments but got 2.");
+        let printed = String::from_utf8(output.0.borrow().clone()).unwrap();
+        assert_eq!(printed, "true\n<native fn>\n42.0\n");
+
+        let mut parser = Parser::new(Scanner::new("double(\"x\");"));
+        let error = interpreter.interpret(&parser.parse()).unwrap_err();
+        assert_eq!(error.message, "Argument must be a number.");
+    }
+
+    #[test]
+    fn test_run_stack_overflow() {
+        let (_, error) = run_with_call_depth("fun f() {\n  f();\n}\nf();", 50);
+        let error = error.unwrap();
+        assert_eq!(error.message, "Stack overflow.");
+        assert_eq!(error.token.line, 2);
+
+        let (output, error) = run_with_call_depth(
+            "fun count(n) { if (n == 0) return 0; return count(n - 1) + 1; }
+             print count(49); print count(50);",
+            50,
+        );
+        assert_eq!(output, "49.0\n");
+        assert_eq!(error.unwrap().message, "Stack overflow.");
+    }
+
+    #[test]
+    fn test_default_call_depth_fits_a_main_thread_stack() {
+        // test threads get less stack than a main thread
+        let main_sized = std::thread::Builder::new().stack_size(8 << 20);
+        let handle = main_sized
+            .spawn(|| {
+                let (_, error) = run("fun f(n) { return f(n + 1); } f(0);");
+                error.map(|error| error.message)
+            })
+            .unwrap();
+        assert_eq!(handle.join().unwrap().as_deref(), Some("Stack overflow."));
+    }
+}
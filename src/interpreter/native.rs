@@ -0,0 +1,55 @@
+use super::{Callable, Interpreter, RuntimeError, Value};
+use crate::domain::token::Token;
+
+/*
+    A NativeFunction wraps a Rust closure so scripts can call it.
+
+    The closure reports failures as a plain message, which becomes a runtime
+    error at the call site.
+*/
+
+pub type NativeFn = dyn Fn(&mut Interpreter, Vec<Value>) -> Result<Value, String>;
+
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: usize,
+    function: Box<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new<F>(name: String, arity: usize, function: F) -> Self
+    where
+        F: Fn(&mut Interpreter, Vec<Value>) -> Result<Value, String> + 'static,
+    {
+        Self {
+            name,
+            arity,
+            function: Box::new(function),
+        }
+    }
+}
+
+impl Callable for NativeFunction {
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        (self.function)(interpreter, arguments)
+            .map_err(|message| RuntimeError::uncoded(message, paren.clone()))
+    }
+}
+
+impl std::fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish()
+    }
+}
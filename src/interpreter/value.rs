@@ -0,0 +1,77 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::{Callable, LoxClass, LoxFunction, LoxInstance};
+use crate::domain::{Literal, Symbol};
+
+/*
+    A Value is what an expression produces at runtime.
+
+    Truthiness follows Lox: `nil` and `false` are falsey, everything else is truthy.
+*/
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Nil,
+    Boolean(bool),
+    Number(f64),
+    // interned, so equal strings compare by pointer
+    String(Symbol),
+    Function(Rc<LoxFunction>),
+    Native(Rc<dyn Callable>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+}
+
+impl Value {
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Boolean(false))
+    }
+}
+
+// values of different types are never equal, functions, natives, classes
+// and instances compare by identity
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Boolean(left), Value::Boolean(right)) => left == right,
+            (Value::Number(left), Value::Number(right)) => left == right,
+            (Value::String(left), Value::String(right)) => left == right,
+            (Value::Function(left), Value::Function(right)) => Rc::ptr_eq(left, right),
+            (Value::Native(left), Value::Native(right)) => Rc::ptr_eq(left, right),
+            (Value::Class(left), Value::Class(right)) => Rc::ptr_eq(left, right),
+            (Value::Instance(left), Value::Instance(right)) => Rc::ptr_eq(left, right),
+            _ => false,
+        }
+    }
+}
+
+impl From<&Literal> for Value {
+    fn from(literal: &Literal) -> Self {
+        match literal {
+            Literal::String(value) => Value::String(value.clone()),
+            Literal::Number(value) => Value::Number(*value),
+            Literal::Boolean(value) => Value::Boolean(*value),
+            // Identifiers never reach the evaluator as literals, the scanner
+            // leaves their literal empty.
+            Literal::Identifier(_) | Literal::Nil => Value::Nil,
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Boolean(boolean) => write!(f, "{}", boolean),
+            // Same formatting as `Literal::Number` so `parse` and `evaluate` agree.
+            Value::Number(number) => write!(f, "{:?}", number),
+            Value::String(string) => write!(f, "{}", string),
+            Value::Function(function) => write!(f, "{}", function),
+            Value::Native(_) => write!(f, "<native fn>"),
+            Value::Class(class) => write!(f, "{}", class),
+            Value::Instance(instance) => write!(f, "{}", instance.borrow()),
+        }
+    }
+}
@@ -1,3 +1,12 @@
+pub mod chunk;
+pub mod compiler;
+pub mod diagnostics;
+pub mod disassembler;
 pub mod domain;
+pub mod interpreter;
 pub mod parser;
+pub mod repl;
+pub mod resolver;
 pub mod scanner;
+pub mod serializer;
+pub mod vm;
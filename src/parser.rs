@@ -1,64 +1,165 @@
-use crate::domain::{token::Token, Expression, Literal, TokenType};
+use std::rc::Rc;
+
+use crate::diagnostics::codes;
+use crate::domain::{
+    token::Token, Expression, FunctionDeclaration, Literal, Span, Stmt, Symbol, TokenType,
+};
+use crate::scanner::ScannerError;
+
+// limit on parameters and arguments of a single call
+const MAX_ARGUMENTS: usize = 255;
 
 pub struct ParserError {
+    pub code: &'static str,
     pub message: String,
     pub token: Token,
 }
 
 impl ParserError {
-    pub fn new(message: String, token: Token) -> Self {
-        Self { message, token }
+    pub fn new(code: &'static str, message: String, token: Token) -> Self {
+        Self {
+            code,
+            message,
+            token,
+        }
     }
 }
 
 impl std::fmt::Display for ParserError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "[line {}] Error: {}", self.token.line, self.message)
+        if self.token.token_type == TokenType::Eof {
+            write!(
+                f,
+                "[line {}] Error at end: {}",
+                self.token.line, self.message
+            )
+        } else {
+            write!(
+                f,
+                "[line {}] Error at '{}': {}",
+                self.token.line, self.token.lexeme, self.message
+            )
+        }
     }
 }
 
-pub struct Parser {
-    pub tokens: Vec<Token>,
-    pub current: usize,
-    pub errors: Vec<ParserError>,
+// anything wrong with the syntax of a script, from either the Scanner or the Parser
+pub enum SyntaxError {
+    Scanner(ScannerError),
+    Parser(ParserError),
 }
 
-impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
-        Self {
+impl std::fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SyntaxError::Scanner(error) => write!(f, "{}", error),
+            SyntaxError::Parser(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/*
+    The Parser pulls tokens from the Scanner as it needs them rather than
+    scanning the whole source first. It only ever looks at the current token
+    and the one before it.
+
+    Scanner errors are recorded as the tokens after them are pulled, so
+    `errors` lists scan and parse errors together in source order.
+*/
+
+pub struct Parser<I> {
+    tokens: I,
+    previous: Token,
+    current: Token,
+    pub errors: Vec<SyntaxError>,
+}
+
+impl<I: Iterator<Item = Result<Token, ScannerError>>> Parser<I> {
+    pub fn new(tokens: I) -> Self {
+        let placeholder = Token::new(
+            TokenType::Eof,
+            Symbol::intern(""),
+            None,
+            1,
+            1,
+            1,
+            Span::default(),
+        );
+        let mut parser = Self {
             tokens,
-            current: 0,
+            previous: placeholder.clone(),
+            current: placeholder,
             errors: Vec::new(),
+        };
+        parser.current = parser.next_token();
+        parser
+    }
+
+    pub fn parse(&mut self) -> Vec<Stmt> {
+        let mut statements: Vec<Stmt> = Vec::new();
+        while !self.is_at_end() {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
         }
+        statements
     }
 
-    pub fn parse(&mut self) -> Vec<Expression> {
+    // used by the `parse` and `evaluate` commands, which work on bare expressions
+    pub fn parse_expressions(&mut self) -> Vec<Expression> {
         let mut expressions: Vec<Expression> = Vec::new();
         while !self.is_at_end() {
-            expressions.push(self.expression());
+            match self.expression() {
+                Some(expression) => expressions.push(expression),
+                None => self.synchronize(),
+            }
         }
         expressions
     }
 
     // basic methods to help with parsing
 
-    fn advance(&mut self) -> Token {
+    fn advance(&mut self) -> &Token {
         if !self.is_at_end() {
-            self.current += 1;
+            let next = self.next_token();
+            self.previous = std::mem::replace(&mut self.current, next);
         }
         self.previous()
     }
 
+    // pulls the next token, recording the scanner errors before it
+    fn next_token(&mut self) -> Token {
+        loop {
+            match self.tokens.next() {
+                Some(Ok(token)) => return token,
+                Some(Err(error)) => self.errors.push(SyntaxError::Scanner(error)),
+                // a token stream always ends with Eof, but don't rely on it
+                None => {
+                    let end = self.current.span.end;
+                    return Token::new(
+                        TokenType::Eof,
+                        Symbol::intern(""),
+                        None,
+                        self.current.line,
+                        self.current.start_line,
+                        self.current.column,
+                        Span::new(end, end),
+                    );
+                }
+            }
+        }
+    }
+
     fn is_at_end(&self) -> bool {
-        self.peek().token_type == TokenType::Eof
+        self.current.token_type == TokenType::Eof
     }
 
-    fn peek(&self) -> Token {
-        self.tokens[self.current].clone()
+    fn peek(&self) -> &Token {
+        &self.current
     }
 
-    fn previous(&self) -> Token {
-        self.tokens[self.current - 1].clone()
+    fn previous(&self) -> &Token {
+        &self.previous
     }
 
     fn advance_for_token_types(&mut self, token_types: Vec<TokenType>) -> bool {
@@ -68,7 +169,66 @@ impl Parser {
                 return true;
             }
         }
-        return false;
+        false
+    }
+
+    // consumes the current token if it has the expected type,
+    // otherwise records an error at it
+    fn consume(
+        &mut self,
+        token_type: TokenType,
+        code: &'static str,
+        message: &str,
+    ) -> Option<Token> {
+        if self.check_future_for_token(token_type) {
+            return Some(self.advance().clone());
+        }
+
+        self.error(code, message.to_string(), self.peek().clone());
+        None
+    }
+
+    fn error(&mut self, code: &'static str, message: String, token: Token) {
+        // the source ended inside an interpolation, which the scanner has
+        // reported, anything missing at the end only follows from that
+        if token.token_type == TokenType::Eof && self.unterminated_interpolation() {
+            return;
+        }
+        self.errors
+            .push(SyntaxError::Parser(ParserError::new(code, message, token)));
+    }
+
+    fn unterminated_interpolation(&self) -> bool {
+        self.errors.iter().any(|error| {
+            matches!(error, SyntaxError::Scanner(error)
+                if error.code == codes::UNTERMINATED_INTERPOLATION)
+        })
+    }
+
+    // Panic-mode recovery: after an error, skip tokens until the start of the
+    // next statement so one mistake is reported once instead of cascading.
+    fn synchronize(&mut self) {
+        self.advance();
+
+        while !self.is_at_end() {
+            if self.previous().token_type == TokenType::Semicolon {
+                return;
+            }
+
+            match self.peek().token_type {
+                TokenType::Class
+                | TokenType::Fun
+                | TokenType::Var
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return => return,
+                _ => {
+                    self.advance();
+                }
+            }
+        }
     }
 
     // will be used to check if the current token is of  the expected type
@@ -89,67 +249,554 @@ Algorithm: Recursive Descent Parsing
 
 */
 
-impl Parser {
-    fn expression(&mut self) -> Expression {
-        self.equality()
+impl<I: Iterator<Item = Result<Token, ScannerError>>> Parser<I> {
+    // every parse error unwinds to here, where the parser resynchronizes
+    fn declaration(&mut self) -> Option<Stmt> {
+        let statement = self.declaration_without_recovery();
+        if statement.is_none() {
+            self.synchronize();
+        }
+        statement
+    }
+
+    fn declaration_without_recovery(&mut self) -> Option<Stmt> {
+        if self.advance_for_token_types(vec![TokenType::Class]) {
+            return self.class_declaration();
+        }
+
+        if self.advance_for_token_types(vec![TokenType::Fun]) {
+            let keyword = self.previous();
+            return Some(Stmt::new_function(self.function("function", keyword.span)?));
+        }
+
+        if self.advance_for_token_types(vec![TokenType::Var]) {
+            return self.var_declaration();
+        }
+
+        self.statement()
+    }
+
+    fn class_declaration(&mut self) -> Option<Stmt> {
+        let start = self.previous().span;
+        let name = self.consume(
+            TokenType::Identifier,
+            codes::EXPECTED_NAME,
+            "Expect class name.",
+        )?;
+
+        let mut superclass = None;
+        if self.advance_for_token_types(vec![TokenType::Less]) {
+            let superclass_name = self.consume(
+                TokenType::Identifier,
+                codes::EXPECTED_NAME,
+                "Expect superclass name.",
+            )?;
+            superclass = Some(Expression::new_variable(superclass_name));
+        }
+
+        self.consume(
+            TokenType::LeftBrace,
+            codes::MISSING_DELIMITER,
+            "Expect '{' before class body.",
+        )?;
+
+        let mut methods = Vec::new();
+        while !self.check_future_for_token(TokenType::RightBrace) && !self.is_at_end() {
+            let start = self.peek().span;
+            methods.push(self.function("method", start)?);
+        }
+
+        let right_brace = self.consume(
+            TokenType::RightBrace,
+            codes::MISSING_DELIMITER,
+            "Expect '}' after class body.",
+        )?;
+        Some(Stmt::new_class(
+            name,
+            superclass,
+            methods,
+            start.to(right_brace.span),
+        ))
+    }
+
+    // kind is only used in error messages, start is where the declaration begins
+    fn function(&mut self, kind: &str, start: Span) -> Option<Rc<FunctionDeclaration>> {
+        let name = self.consume(
+            TokenType::Identifier,
+            codes::EXPECTED_NAME,
+            &format!("Expect {} name.", kind),
+        )?;
+        self.consume(
+            TokenType::LeftParen,
+            codes::MISSING_DELIMITER,
+            &format!("Expect '(' after {} name.", kind),
+        )?;
+
+        let mut params = Vec::new();
+        if !self.check_future_for_token(TokenType::RightParen) {
+            loop {
+                if params.len() >= MAX_ARGUMENTS {
+                    self.error(
+                        codes::TOO_MANY_ARGUMENTS,
+                        format!("Can't have more than {} parameters.", MAX_ARGUMENTS),
+                        self.peek().clone(),
+                    );
+                }
+
+                params.push(self.consume(
+                    TokenType::Identifier,
+                    codes::EXPECTED_NAME,
+                    "Expect parameter name.",
+                )?);
+
+                if !self.advance_for_token_types(vec![TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(
+            TokenType::RightParen,
+            codes::MISSING_DELIMITER,
+            "Expect ')' after parameters.",
+        )?;
+
+        self.consume(
+            TokenType::LeftBrace,
+            codes::MISSING_DELIMITER,
+            &format!("Expect '{{' before {} body.", kind),
+        )?;
+        let body = self.block()?;
+
+        Some(Rc::new(FunctionDeclaration {
+            name,
+            params,
+            body,
+            span: start.to(self.previous().span),
+        }))
+    }
+
+    fn var_declaration(&mut self) -> Option<Stmt> {
+        let start = self.previous().span;
+        let name = self.consume(
+            TokenType::Identifier,
+            codes::EXPECTED_NAME,
+            "Expect variable name.",
+        )?;
+
+        let mut initializer = None;
+        if self.advance_for_token_types(vec![TokenType::Equal]) {
+            initializer = Some(self.expression()?);
+        }
+
+        let semicolon = self.consume(
+            TokenType::Semicolon,
+            codes::MISSING_SEMICOLON,
+            "Expect ';' after variable declaration.",
+        )?;
+        Some(Stmt::new_var(name, initializer, start.to(semicolon.span)))
+    }
+
+    fn statement(&mut self) -> Option<Stmt> {
+        if self.advance_for_token_types(vec![TokenType::For]) {
+            return self.for_statement();
+        }
+
+        if self.advance_for_token_types(vec![TokenType::If]) {
+            return self.if_statement();
+        }
+
+        if self.advance_for_token_types(vec![TokenType::Print]) {
+            return self.print_statement();
+        }
+
+        if self.advance_for_token_types(vec![TokenType::Return]) {
+            return self.return_statement();
+        }
+
+        if self.advance_for_token_types(vec![TokenType::While]) {
+            return self.while_statement();
+        }
+
+        if self.advance_for_token_types(vec![TokenType::LeftBrace]) {
+            let start = self.previous().span;
+            let statements = self.block()?;
+            let span = start.to(self.previous().span);
+            return Some(Stmt::new_block(statements, span));
+        }
+
+        self.expression_statement()
+    }
+
+    // there is no for node in the tree, the loop is desugared into
+    // { initializer; while (condition) { body; increment; } }
+    fn for_statement(&mut self) -> Option<Stmt> {
+        let start = self.previous().span;
+        self.consume(
+            TokenType::LeftParen,
+            codes::MISSING_DELIMITER,
+            "Expect '(' after 'for'.",
+        )?;
+
+        let initializer = if self.advance_for_token_types(vec![TokenType::Semicolon]) {
+            None
+        } else if self.advance_for_token_types(vec![TokenType::Var]) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let mut condition = None;
+        if !self.check_future_for_token(TokenType::Semicolon) {
+            condition = Some(self.expression()?);
+        }
+        let semicolon = self.consume(
+            TokenType::Semicolon,
+            codes::MISSING_SEMICOLON,
+            "Expect ';' after loop condition.",
+        )?;
+
+        let mut increment = None;
+        if !self.check_future_for_token(TokenType::RightParen) {
+            increment = Some(self.expression()?);
+        }
+        self.consume(
+            TokenType::RightParen,
+            codes::MISSING_DELIMITER,
+            "Expect ')' after for clauses.",
+        )?;
+
+        let mut body = self.statement()?;
+        // the desugared nodes all cover the whole loop
+        let span = start.to(body.span());
+
+        if let Some(increment) = increment {
+            let increment_span = increment.span();
+            body = Stmt::new_block(
+                vec![body, Stmt::new_expression(increment, increment_span)],
+                span,
+            );
+        }
+
+        // a missing condition sits where it would have been written
+        let condition = condition.unwrap_or_else(|| {
+            let empty = Span::new(semicolon.span.start, semicolon.span.start);
+            Expression::new_literal(Literal::Boolean(true), empty)
+        });
+        body = Stmt::new_while(condition, Box::new(body), span);
+
+        // the block keeps the loop variable scoped to the loop
+        if let Some(initializer) = initializer {
+            body = Stmt::new_block(vec![initializer, body], span);
+        }
+
+        Some(body)
+    }
+
+    // the else is claimed by the nearest if, which resolves the dangling else
+    fn if_statement(&mut self) -> Option<Stmt> {
+        let start = self.previous().span;
+        self.consume(
+            TokenType::LeftParen,
+            codes::MISSING_DELIMITER,
+            "Expect '(' after 'if'.",
+        )?;
+        let condition = self.expression()?;
+        self.consume(
+            TokenType::RightParen,
+            codes::MISSING_DELIMITER,
+            "Expect ')' after if condition.",
+        )?;
+
+        let then_branch = Box::new(self.statement()?);
+        let mut else_branch = None;
+        if self.advance_for_token_types(vec![TokenType::Else]) {
+            else_branch = Some(Box::new(self.statement()?));
+        }
+
+        let end = match &else_branch {
+            Some(else_branch) => else_branch.span(),
+            None => then_branch.span(),
+        };
+        Some(Stmt::new_if(
+            condition,
+            then_branch,
+            else_branch,
+            start.to(end),
+        ))
+    }
+
+    fn return_statement(&mut self) -> Option<Stmt> {
+        let keyword = self.previous().clone();
+
+        let mut value = None;
+        if !self.check_future_for_token(TokenType::Semicolon) {
+            value = Some(self.expression()?);
+        }
+
+        let semicolon = self.consume(
+            TokenType::Semicolon,
+            codes::MISSING_SEMICOLON,
+            "Expect ';' after return value.",
+        )?;
+        let span = keyword.span.to(semicolon.span);
+        Some(Stmt::new_return(keyword, value, span))
+    }
+
+    fn while_statement(&mut self) -> Option<Stmt> {
+        let start = self.previous().span;
+        self.consume(
+            TokenType::LeftParen,
+            codes::MISSING_DELIMITER,
+            "Expect '(' after 'while'.",
+        )?;
+        let condition = self.expression()?;
+        self.consume(
+            TokenType::RightParen,
+            codes::MISSING_DELIMITER,
+            "Expect ')' after condition.",
+        )?;
+        let body = self.statement()?;
+
+        let span = start.to(body.span());
+        Some(Stmt::new_while(condition, Box::new(body), span))
+    }
+
+    fn print_statement(&mut self) -> Option<Stmt> {
+        let start = self.previous().span;
+        let value = self.expression()?;
+        let semicolon = self.consume(
+            TokenType::Semicolon,
+            codes::MISSING_SEMICOLON,
+            "Expect ';' after value.",
+        )?;
+        Some(Stmt::new_print(value, start.to(semicolon.span)))
+    }
+
+    fn expression_statement(&mut self) -> Option<Stmt> {
+        let expression = self.expression()?;
+        let semicolon = self.consume(
+            TokenType::Semicolon,
+            codes::MISSING_SEMICOLON,
+            "Expect ';' after expression.",
+        )?;
+        let span = expression.span().to(semicolon.span);
+        Some(Stmt::new_expression(expression, span))
+    }
+
+    // on success the closing brace is the previous token
+    fn block(&mut self) -> Option<Vec<Stmt>> {
+        let mut statements = Vec::new();
+
+        while !self.check_future_for_token(TokenType::RightBrace) && !self.is_at_end() {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
+        }
+
+        self.consume(
+            TokenType::RightBrace,
+            codes::MISSING_DELIMITER,
+            "Expect '}' after block.",
+        )?;
+        Some(statements)
+    }
+
+    fn expression(&mut self) -> Option<Expression> {
+        self.assignment()
+    }
+
+    // assignment is right-associative, so the value is parsed recursively;
+    // the left side is parsed as an ordinary expression and then checked
+    // to be a valid target
+    fn assignment(&mut self) -> Option<Expression> {
+        let expression = self.or()?;
+
+        if self.advance_for_token_types(vec![TokenType::Equal]) {
+            let equals = self.previous().clone();
+            let value = self.assignment()?;
+
+            match expression {
+                Expression::Variable { name, .. } => {
+                    return Some(Expression::new_assign(name, Box::new(value)));
+                }
+                Expression::Get { object, name, .. } => {
+                    return Some(Expression::new_set(object, name, Box::new(value)));
+                }
+                _ => {}
+            }
+
+            // the parser is not confused here, so report without bailing out
+            self.error(
+                codes::INVALID_ASSIGNMENT_TARGET,
+                "Invalid assignment target.".to_string(),
+                equals,
+            );
+        }
+
+        Some(expression)
     }
 
-    fn equality(&mut self) -> Expression {
-        let mut expression: Expression = self.comparison();
+    fn or(&mut self) -> Option<Expression> {
+        let mut expression = self.and()?;
+
+        while self.advance_for_token_types(vec![TokenType::Or]) {
+            let operator = self.previous().clone();
+            let right = self.and()?;
+            expression = Expression::new_logical(Box::new(expression), operator, Box::new(right));
+        }
+
+        Some(expression)
+    }
+
+    fn and(&mut self) -> Option<Expression> {
+        let mut expression = self.equality()?;
+
+        while self.advance_for_token_types(vec![TokenType::And]) {
+            let operator = self.previous().clone();
+            let right = self.equality()?;
+            expression = Expression::new_logical(Box::new(expression), operator, Box::new(right));
+        }
+
+        Some(expression)
+    }
+
+    fn equality(&mut self) -> Option<Expression> {
+        let mut expression: Expression = self.comparison()?;
 
         while self.advance_for_token_types(vec![TokenType::BangEqual, TokenType::EqualEqual]) {
-            let operator: Token = self.previous();
-            let right: Expression = self.comparison();
+            let operator = self.previous().clone();
+            let right: Expression = self.comparison()?;
             expression = Expression::new_binary(Box::new(expression), operator, Box::new(right));
         }
 
-        expression
+        Some(expression)
     }
 
-    fn comparison(&mut self) -> Expression {
-        let mut expression = self.term();
+    fn comparison(&mut self) -> Option<Expression> {
+        let mut expression = self.term()?;
         while self.advance_for_token_types(vec![
             TokenType::Greater,
             TokenType::GreaterEqual,
             TokenType::Less,
             TokenType::LessEqual,
         ]) {
-            let operator = self.previous();
-            let right = self.term();
+            let operator = self.previous().clone();
+            let right = self.term()?;
             expression = Expression::new_binary(Box::new(expression), operator, Box::new(right));
         }
-        expression
+        Some(expression)
     }
 
-    fn term(&mut self) -> Expression {
-        let mut expression = self.factor();
+    fn term(&mut self) -> Option<Expression> {
+        let mut expression = self.factor()?;
         while self.advance_for_token_types(vec![TokenType::Minus, TokenType::Plus]) {
-            let operator = self.previous();
-            let right = self.factor();
+            let operator = self.previous().clone();
+            let right = self.factor()?;
             expression = Expression::new_binary(Box::new(expression), operator, Box::new(right));
         }
-        expression
+        Some(expression)
     }
 
-    fn factor(&mut self) -> Expression {
-        let mut expression = self.unary();
+    fn factor(&mut self) -> Option<Expression> {
+        let mut expression = self.unary()?;
         while self.advance_for_token_types(vec![TokenType::Slash, TokenType::Star]) {
-            let operator = self.previous();
-            let right = self.unary();
+            let operator = self.previous().clone();
+            let right = self.unary()?;
             expression = Expression::new_binary(Box::new(expression), operator, Box::new(right));
         }
-        expression
+        Some(expression)
     }
 
-    fn unary(&mut self) -> Expression {
+    fn unary(&mut self) -> Option<Expression> {
         if self.advance_for_token_types(vec![TokenType::Bang, TokenType::Minus]) {
-            let operator = self.previous();
-            let right = self.unary();
-            return Expression::new_unary(operator, Box::new(right));
+            let operator = self.previous().clone();
+            let right = self.unary()?;
+            return Some(Expression::new_unary(operator, Box::new(right)));
         }
 
-        self.primary()
-            .unwrap_or(Expression::new_literal(Literal::Nil))
+        self.call()
+    }
+
+    fn call(&mut self) -> Option<Expression> {
+        let mut expression = self.primary()?;
+
+        loop {
+            if self.advance_for_token_types(vec![TokenType::LeftParen]) {
+                expression = self.finish_call(expression)?;
+            } else if self.advance_for_token_types(vec![TokenType::Dot]) {
+                let name = self.consume(
+                    TokenType::Identifier,
+                    codes::EXPECTED_NAME,
+                    "Expect property name after '.'.",
+                )?;
+                expression = Expression::new_get(Box::new(expression), name);
+            } else {
+                break;
+            }
+        }
+
+        Some(expression)
+    }
+
+    fn finish_call(&mut self, callee: Expression) -> Option<Expression> {
+        let mut arguments = Vec::new();
+        if !self.check_future_for_token(TokenType::RightParen) {
+            loop {
+                // reported at the offending argument, parsing carries on
+                if arguments.len() >= MAX_ARGUMENTS {
+                    self.error(
+                        codes::TOO_MANY_ARGUMENTS,
+                        format!("Can't have more than {} arguments.", MAX_ARGUMENTS),
+                        self.peek().clone(),
+                    );
+                }
+
+                arguments.push(self.expression()?);
+
+                if !self.advance_for_token_types(vec![TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+
+        let paren = self.consume(
+            TokenType::RightParen,
+            codes::MISSING_DELIMITER,
+            "Expect ')' after arguments.",
+        )?;
+        Some(Expression::new_call(Box::new(callee), paren, arguments))
+    }
+
+    // `"a ${b} c"`, called with the first segment just consumed
+    fn interpolation(&mut self) -> Option<Expression> {
+        let start = self.previous().span;
+        let mut parts = Vec::new();
+        loop {
+            // empty segments, like the one before `${` in `"${a}"`, are left out
+            let segment = self.previous();
+            if let Some(Literal::String(value)) = &segment.literal {
+                if !value.is_empty() {
+                    parts.push(Expression::new_literal(
+                        Literal::String(value.clone()),
+                        segment.span,
+                    ));
+                }
+            }
+
+            // the closing segment
+            if segment.token_type == TokenType::String {
+                return Some(Expression::new_interpolation(parts, start.to(segment.span)));
+            }
+
+            parts.push(self.expression()?);
+            if !self.advance_for_token_types(vec![TokenType::Interpolation]) {
+                self.consume(
+                    TokenType::String,
+                    codes::MISSING_DELIMITER,
+                    "Expect '}' after interpolated expression.",
+                )?;
+            }
+        }
     }
 
     fn primary(&mut self) -> Option<Expression> {
@@ -160,7 +807,8 @@ impl Parser {
             TokenType::Number,
             TokenType::String,
         ]) {
-            match self.previous().literal.unwrap() {
+            let span = self.previous().span;
+            match self.previous().literal.clone().unwrap() {
                 // if the literal is a number, we will return a number expression
                 // with the value of the number
                 // if the literal is a string, we will return a string expression
@@ -172,38 +820,175 @@ impl Parser {
                 // if the literal is a identifier, we will return an expression
                 // with the value of the identifier
                 Literal::Number(value) => {
-                    return Some(Expression::new_literal(Literal::Number(value)))
+                    return Some(Expression::new_literal(Literal::Number(value), span))
                 }
                 Literal::String(value) => {
-                    return Some(Expression::new_literal(Literal::String(value)))
+                    return Some(Expression::new_literal(Literal::String(value), span))
                 }
                 Literal::Boolean(value) => {
-                    return Some(Expression::new_literal(Literal::Boolean(value)))
+                    return Some(Expression::new_literal(Literal::Boolean(value), span))
                 }
-                Literal::Nil => return Some(Expression::new_literal(Literal::Nil)),
+                Literal::Nil => return Some(Expression::new_literal(Literal::Nil, span)),
                 Literal::Identifier(value) => {
-                    return Some(Expression::new_literal(Literal::Identifier(value)))
+                    return Some(Expression::new_literal(Literal::Identifier(value), span))
                 }
             };
         }
 
+        if self.advance_for_token_types(vec![TokenType::Interpolation]) {
+            return self.interpolation();
+        }
+
+        if self.advance_for_token_types(vec![TokenType::Super]) {
+            let keyword = self.previous().clone();
+            self.consume(
+                TokenType::Dot,
+                codes::MISSING_DELIMITER,
+                "Expect '.' after 'super'.",
+            )?;
+            let method = self.consume(
+                TokenType::Identifier,
+                codes::EXPECTED_NAME,
+                "Expect superclass method name.",
+            )?;
+            return Some(Expression::new_super(keyword, method));
+        }
+
+        if self.advance_for_token_types(vec![TokenType::This]) {
+            return Some(Expression::new_this(self.previous().clone()));
+        }
+
+        if self.advance_for_token_types(vec![TokenType::Identifier]) {
+            return Some(Expression::new_variable(self.previous().clone()));
+        }
+
         if self.advance_for_token_types(vec![TokenType::LeftParen]) {
-            let expression = self.expression();
-            if self.check_future_for_token(TokenType::RightParen) {
-                self.advance();
-                return Some(Expression::new_grouping(expression));
-            }
-            self.errors.push(ParserError {
-                message: "Expect ')' after expression.".to_string(),
-                token: self.peek(),
-            });
+            let start = self.previous().span;
+            let expression = self.expression()?;
+            let right_paren = self.consume(
+                TokenType::RightParen,
+                codes::UNCLOSED_PARENTHESIS,
+                "Expect ')' after expression.",
+            )?;
+            return Some(Expression::new_grouping(
+                expression,
+                start.to(right_paren.span),
+            ));
         }
 
-        self.errors.push(ParserError {
-            message: "".to_string(),
-            token: self.peek(),
-        });
+        self.error(
+            codes::EXPECTED_EXPRESSION,
+            "Expect expression.".to_string(),
+            self.peek().clone(),
+        );
 
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    fn parse_errors(source: &str) -> Vec<String> {
+        let mut parser = Parser::new(Scanner::new(source));
+        parser.parse();
+        parser
+            .errors
+            .iter()
+            .map(|error| error.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_reports_each_independent_error_once() {
+        let errors = parse_errors("var x = ;\nprint 1 +;\nvar ok = 1;\nprint (2;");
+        assert_eq!(
+            errors,
+            vec![
+                "[line 1] Error at ';': Expect expression.",
+                "[line 2] Error at ';': Expect expression.",
+                "[line 4] Error at ';': Expect ')' after expression.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_recovers_inside_blocks() {
+        let errors = parse_errors("fun f() {\n  x = ;\n  1 = 2;\n}\nprint");
+        assert_eq!(
+            errors,
+            vec![
+                "[line 2] Error at ';': Expect expression.",
+                "[line 3] Error at '=': Invalid assignment target.",
+                "[line 5] Error at end: Expect expression.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_interleaves_scanner_errors_in_source_order() {
+        let errors = parse_errors("print 1 +;\nvar @ = 2;\nprint \"ok\" # 3;\nprint (4;");
+        assert_eq!(
+            errors,
+            vec![
+                "[line 1] Error at ';': Expect expression.",
+                "[line 2] Error: Unexpected character: @",
+                "[line 2] Error at '=': Expect variable name.",
+                "[line 3] Error: Unexpected character: #",
+                "[line 3] Error at '3': Expect ';' after value.",
+                "[line 4] Error at ';': Expect ')' after expression.",
+            ]
+        );
+    }
+
+    fn parse_codes(source: &str) -> Vec<&'static str> {
+        let mut parser = Parser::new(Scanner::new(source));
+        parser.parse();
+        parser
+            .errors
+            .iter()
+            .map(|error| match error {
+                SyntaxError::Scanner(error) => error.code,
+                SyntaxError::Parser(error) => error.code,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_reports_an_open_interpolation_once() {
+        // the `"` meant to close the string opens another one instead
+        assert_eq!(
+            parse_codes("var price = 1;\nprint \"total: ${price * 2\";"),
+            [codes::UNTERMINATED_INTERPOLATION]
+        );
+        assert_eq!(
+            parse_codes("print \"a ${b + \"c ${d\" + \""),
+            [codes::UNTERMINATED_INTERPOLATION]
+        );
+        // errors before the end of input are still reported
+        assert_eq!(
+            parse_codes("print \"a ${b c\";"),
+            [codes::MISSING_DELIMITER, codes::UNTERMINATED_INTERPOLATION]
+        );
+    }
+
+    #[test]
+    fn test_parse_spans_cover_source() {
+        let source = "print (1 + 2);\nif (a) {\n  b = \"x\ny\";\n}";
+        let statements = Parser::new(Scanner::new(source)).parse();
+
+        assert_eq!(
+            &source[statements[0].span().start..statements[0].span().end],
+            "print (1 + 2);"
+        );
+        let Stmt::Print { expression, .. } = &statements[0] else {
+            panic!("expected a print statement");
+        };
+        assert_eq!(expression.span(), Span::new(6, 13));
+
+        let if_span = statements[1].span();
+        assert_eq!(&source[if_span.start..if_span.end], &source[15..]);
+    }
+}
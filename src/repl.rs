@@ -0,0 +1,174 @@
+use std::io::{self, BufRead, Write};
+
+use crate::diagnostics::{Emitter, ErrorFormat};
+use crate::domain::{Stmt, TokenType};
+use crate::interpreter::Interpreter;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner::Scanner;
+
+/*
+    The Repl reads a program one entry at a time and runs it against a single
+    Interpreter, so globals defined by earlier entries stay around.
+
+    An entry keeps reading lines while its braces or parens are unbalanced.
+    An entry that is just an expression, with or without the trailing `;`,
+    has its value printed. Errors are reported and the session carries on.
+*/
+
+const PROMPT: &str = "> ";
+const CONTINUATION_PROMPT: &str = "... ";
+
+pub struct Repl {
+    pub interpreter: Interpreter,
+    pub error_format: ErrorFormat,
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        Self::with_interpreter(Interpreter::new())
+    }
+
+    pub fn with_interpreter(interpreter: Interpreter) -> Self {
+        Self {
+            interpreter,
+            error_format: ErrorFormat::Plain,
+        }
+    }
+
+    pub fn run(&mut self) -> io::Result<()> {
+        let stdin = io::stdin();
+        let mut input = stdin.lock();
+        let mut buffer = String::new();
+
+        loop {
+            let prompt = if buffer.is_empty() {
+                PROMPT
+            } else {
+                CONTINUATION_PROMPT
+            };
+            print!("{}", prompt);
+            io::stdout().flush()?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                // end of input, run whatever is left over
+                if !buffer.trim().is_empty() {
+                    self.execute(&buffer);
+                }
+                println!();
+                return Ok(());
+            }
+
+            buffer.push_str(&line);
+            if Self::is_incomplete(&buffer) {
+                continue;
+            }
+
+            if !buffer.trim().is_empty() {
+                self.execute(&buffer);
+            }
+            buffer.clear();
+        }
+    }
+
+    // an entry is incomplete while it has more opening than closing braces or parens
+    pub fn is_incomplete(source: &str) -> bool {
+        let mut scanner = Scanner::new(source);
+        scanner.scan_tokens();
+
+        let mut depth: i32 = 0;
+        for token in &scanner.tokens {
+            match token.token_type {
+                TokenType::LeftBrace | TokenType::LeftParen => depth += 1,
+                TokenType::RightBrace | TokenType::RightParen => depth -= 1,
+                _ => {}
+            }
+        }
+
+        depth > 0
+    }
+
+    // runs one entry, reporting errors on stderr instead of stopping
+    pub fn execute(&mut self, source: &str) {
+        let emitter = Emitter::new(self.error_format, "<repl>", source);
+        let Some(statements) = Self::parse(source, &emitter) else {
+            return;
+        };
+
+        let mut resolver = Resolver::new();
+        resolver.resolve(&statements);
+
+        if !resolver.errors.is_empty() {
+            emitter.emit_all(&resolver.errors);
+            return;
+        }
+
+        self.interpreter.resolve(resolver.locals);
+        if let Err(error) = self.interpreter.interpret(&statements) {
+            emitter.emit(&error);
+        }
+    }
+
+    // a lone expression becomes a print statement so its value is shown
+    fn parse(source: &str, emitter: &Emitter) -> Option<Vec<Stmt>> {
+        let mut parser = Parser::new(Scanner::new(source));
+        let statements = parser.parse();
+
+        if parser.errors.is_empty() {
+            if let [Stmt::Expression { expression, span }] = statements.as_slice() {
+                return Some(vec![Stmt::new_print(expression.clone(), *span)]);
+            }
+            return Some(statements);
+        }
+
+        // without the `;` the entry may still be a single expression
+        let mut expression_parser = Parser::new(Scanner::new(source));
+        let mut expressions = expression_parser.parse_expressions();
+        if expression_parser.errors.is_empty() && expressions.len() == 1 {
+            let expression = expressions.remove(0);
+            let span = expression.span();
+            return Some(vec![Stmt::new_print(expression, span)]);
+        }
+
+        emitter.emit_all(&parser.errors);
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Symbol;
+    use crate::interpreter::Value;
+
+    #[test]
+    fn test_is_incomplete() {
+        assert!(Repl::is_incomplete("fun f() {"));
+        assert!(Repl::is_incomplete("print (1 +"));
+        assert!(!Repl::is_incomplete("fun f() { print 1; }"));
+        assert!(!Repl::is_incomplete("}"));
+    }
+
+    #[test]
+    fn test_execute_keeps_globals_across_errors() {
+        let mut repl = Repl::with_interpreter(Interpreter::with_output(Box::new(io::sink())));
+        repl.execute("var a = 1;");
+        repl.execute("a = a + \"x\";");
+        repl.execute("var b = ;");
+        repl.execute("a = a + 1");
+
+        let value = repl
+            .interpreter
+            .globals
+            .borrow()
+            .lookup(&Symbol::intern("a"));
+        assert_eq!(value, Some(Value::Number(2.0)));
+    }
+}
@@ -0,0 +1,395 @@
+use std::collections::HashMap;
+
+use crate::diagnostics::codes;
+use crate::domain::{token::Token, Expression, FunctionDeclaration, Stmt, Symbol};
+
+/*
+    The Resolver walks the syntax tree once before it is executed and works out,
+    for every variable reference, how many scopes separate it from its declaration.
+
+    The Interpreter uses these distances to look variables up in the right
+    environment, so a closure keeps seeing the binding that was in scope where
+    it was written. References that are not recorded are globals.
+
+    Reference - https://craftinginterpreters.com/resolving-and-binding.html
+*/
+
+#[derive(Debug, Clone)]
+pub struct ResolverError {
+    pub code: &'static str,
+    pub message: String,
+    pub token: Token,
+}
+
+impl ResolverError {
+    pub fn new(code: &'static str, message: String, token: Token) -> Self {
+        Self {
+            code,
+            message,
+            token,
+        }
+    }
+}
+
+impl std::fmt::Display for ResolverError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[line {}] Error at '{}': {}",
+            self.token.line, self.token.lexeme, self.message
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+pub struct Resolver {
+    // each scope maps a name to whether its initializer has finished
+    scopes: Vec<HashMap<Symbol, bool>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    // expression id -> number of scopes between the use and the declaration
+    pub locals: HashMap<usize, usize>,
+    pub errors: Vec<ResolverError>,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self {
+            scopes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            locals: HashMap::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn resolve(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            self.resolve_statement(statement);
+        }
+    }
+
+    fn resolve_statement(&mut self, statement: &Stmt) {
+        match statement {
+            Stmt::Block { statements, .. } => {
+                self.begin_scope();
+                self.resolve(statements);
+                self.end_scope();
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } => {
+                let enclosing_class = self.current_class;
+                self.current_class = ClassType::Class;
+
+                self.declare(name);
+                self.define(name);
+
+                if let Some(superclass) = superclass {
+                    if let Expression::Variable {
+                        name: superclass_name,
+                        ..
+                    } = superclass
+                    {
+                        if superclass_name.lexeme == name.lexeme {
+                            self.errors.push(ResolverError::new(
+                                codes::INHERITS_FROM_ITSELF,
+                                "A class can't inherit from itself.".to_string(),
+                                superclass_name.clone(),
+                            ));
+                        }
+                    }
+
+                    self.current_class = ClassType::Subclass;
+                    self.resolve_expression(superclass);
+
+                    // `super` lives in its own scope around the one holding `this`
+                    self.begin_scope();
+                    if let Some(scope) = self.scopes.last_mut() {
+                        scope.insert(Symbol::intern("super"), true);
+                    }
+                }
+
+                // methods close over a scope that holds `this`
+                self.begin_scope();
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.insert(Symbol::intern("this"), true);
+                }
+
+                for method in methods {
+                    let function_type = if method.name.lexeme == "init" {
+                        FunctionType::Initializer
+                    } else {
+                        FunctionType::Method
+                    };
+                    self.resolve_function(method, function_type);
+                }
+
+                self.end_scope();
+
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+
+                self.current_class = enclosing_class;
+            }
+            Stmt::Expression { expression, .. } | Stmt::Print { expression, .. } => {
+                self.resolve_expression(expression);
+            }
+            Stmt::Function(declaration) => {
+                // declared eagerly so the function can refer to itself
+                self.declare(&declaration.name);
+                self.define(&declaration.name);
+                self.resolve_function(declaration, FunctionType::Function);
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.resolve_expression(condition);
+                self.resolve_statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_statement(else_branch);
+                }
+            }
+            Stmt::Return { keyword, value, .. } => {
+                if self.current_function == FunctionType::None {
+                    self.errors.push(ResolverError::new(
+                        codes::TOP_LEVEL_RETURN,
+                        "Can't return from top-level code.".to_string(),
+                        keyword.clone(),
+                    ));
+                }
+
+                if let Some(value) = value {
+                    if self.current_function == FunctionType::Initializer {
+                        self.errors.push(ResolverError::new(
+                            codes::RETURN_FROM_INITIALIZER,
+                            "Can't return a value from an initializer.".to_string(),
+                            keyword.clone(),
+                        ));
+                    }
+
+                    self.resolve_expression(value);
+                }
+            }
+            Stmt::Var {
+                name, initializer, ..
+            } => {
+                self.declare(name);
+                if let Some(initializer) = initializer {
+                    self.resolve_expression(initializer);
+                }
+                self.define(name);
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                self.resolve_expression(condition);
+                self.resolve_statement(body);
+            }
+        }
+    }
+
+    fn resolve_expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Assign {
+                id, name, value, ..
+            } => {
+                self.resolve_expression(value);
+                self.resolve_local(*id, name);
+            }
+            Expression::Binary { left, right, .. } | Expression::Logical { left, right, .. } => {
+                self.resolve_expression(left);
+                self.resolve_expression(right);
+            }
+            Expression::Call {
+                callee, arguments, ..
+            } => {
+                self.resolve_expression(callee);
+                for argument in arguments {
+                    self.resolve_expression(argument);
+                }
+            }
+            Expression::Get { object, .. } => self.resolve_expression(object),
+            Expression::Interpolation { parts, .. } => {
+                for part in parts {
+                    self.resolve_expression(part);
+                }
+            }
+            Expression::Set { object, value, .. } => {
+                self.resolve_expression(value);
+                self.resolve_expression(object);
+            }
+            Expression::Super { id, keyword, .. } => {
+                let message = match self.current_class {
+                    ClassType::None => Some("Can't use 'super' outside of a class."),
+                    ClassType::Class => Some("Can't use 'super' in a class with no superclass."),
+                    ClassType::Subclass => None,
+                };
+
+                if let Some(message) = message {
+                    self.errors.push(ResolverError::new(
+                        codes::INVALID_SUPER,
+                        message.to_string(),
+                        keyword.clone(),
+                    ));
+                    return;
+                }
+
+                self.resolve_local(*id, keyword);
+            }
+            Expression::This { id, keyword, .. } => {
+                if self.current_class == ClassType::None {
+                    self.errors.push(ResolverError::new(
+                        codes::THIS_OUTSIDE_CLASS,
+                        "Can't use 'this' outside of a class.".to_string(),
+                        keyword.clone(),
+                    ));
+                    return;
+                }
+
+                self.resolve_local(*id, keyword);
+            }
+            Expression::Grouping { expression, .. } => self.resolve_expression(expression),
+            Expression::Unary { right, .. } => self.resolve_expression(right),
+            Expression::Literal { .. } => {}
+            Expression::Variable { id, name, .. } => {
+                let declared_but_undefined = self
+                    .scopes
+                    .last()
+                    .is_some_and(|scope| scope.get(&name.lexeme) == Some(&false));
+                if declared_but_undefined {
+                    self.errors.push(ResolverError::new(
+                        codes::READ_IN_OWN_INITIALIZER,
+                        "Can't read local variable in its own initializer.".to_string(),
+                        name.clone(),
+                    ));
+                }
+
+                self.resolve_local(*id, name);
+            }
+        }
+    }
+
+    fn resolve_function(&mut self, declaration: &FunctionDeclaration, function_type: FunctionType) {
+        let enclosing_function = self.current_function;
+        self.current_function = function_type;
+
+        self.begin_scope();
+        for param in &declaration.params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve(&declaration.body);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+    }
+
+    // helpers to track the scopes
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+
+        if scope.contains_key(&name.lexeme) {
+            self.errors.push(ResolverError::new(
+                codes::ALREADY_DECLARED,
+                "Already a variable with this name in this scope.".to_string(),
+                name.clone(),
+            ));
+        }
+
+        scope.insert(name.lexeme.clone(), false);
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone(), true);
+        }
+    }
+
+    fn resolve_local(&mut self, id: usize, name: &Token) {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if scope.contains_key(&name.lexeme) {
+                self.locals.insert(id, depth);
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn resolve(source: &str) -> Vec<String> {
+        let statements = Parser::new(Scanner::new(source)).parse();
+
+        let mut resolver = Resolver::new();
+        resolver.resolve(&statements);
+        resolver
+            .errors
+            .iter()
+            .map(|error| error.message.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_resolve_static_errors() {
+        assert_eq!(
+            resolve("{ var a = a; }"),
+            vec!["Can't read local variable in its own initializer."]
+        );
+        assert_eq!(
+            resolve("fun f() { var a; var a; }"),
+            vec!["Already a variable with this name in this scope."]
+        );
+        assert_eq!(
+            resolve("return 1;"),
+            vec!["Can't return from top-level code."]
+        );
+    }
+
+    #[test]
+    fn test_resolve_globals_are_not_errors() {
+        assert!(resolve("var a = 1; var a = a; fun f() { return a; }").is_empty());
+    }
+}
@@ -1,150 +1,208 @@
+use crate::diagnostics::codes;
 use crate::domain::token::Token;
 use crate::domain::token_type::TokenType;
-use crate::domain::Literal;
+use crate::domain::{Literal, Span, Symbol};
 
 /*
     The Scanner is responsible for converting the source code into a sequence of tokens.
-    The Scanner will read the source code character by character and convert it into tokens.
+    The Scanner walks the UTF-8 bytes of the borrowed source and converts them into tokens.
+
+    `start` and `current` are byte offsets into the source, so a lexeme is a
+    slice of it and is interned without being copied. Only literal values
+    allocate. Everything Lox gives a meaning to is ASCII, other characters only
+    show up inside strings, comments and identifiers, and in error messages.
+
+    A string with `${expr}` in it is split up: each part before a `${` is an
+    Interpolation token, followed by the tokens of the expression, and the
+    part after the last `}` is the closing String token. The scanner keeps a
+    stack of the interpolations it is inside, with the braces opened in each,
+    so the `}` that resumes the string is told apart from one closing a block,
+    and strings can nest inside interpolations. A literal `${` is written `\${`.
+
+    Block comments `/* ... */` nest, each `/*` inside one needs its own `*/`.
+
+    The Scanner is an iterator of tokens and errors, in source order, ending
+    with a single Eof token. The Parser pulls from it lazily, `scan_tokens`
+    collects everything into `tokens` and `errors` at once.
 
     Reference - https://craftinginterpreters.com/scanning.html#recognizing-lexemes
 */
 
 #[derive(Debug, Clone)]
-pub struct Scanner {
-    pub source: Vec<char>,
+pub struct Scanner<'a> {
+    pub source: &'a str,
     pub tokens: Vec<Token>,
     pub start: usize,
     pub current: usize,
     pub line: u32,
+    // number of characters consumed on the current line
     pub column: u32,
+    // where the current lexeme starts
+    pub start_line: u32,
+    pub start_column: u32,
     pub errors: Vec<ScannerError>,
+    // the interpolations the scanner is inside, innermost last
+    interpolations: Vec<OpenInterpolation>,
+    // a token to hand out after the error reported just before it
+    pending: Option<Token>,
+    // whether the Eof token has been handed out
+    finished: bool,
+}
+
+// a `${` whose closing `}` hasn't been reached yet
+#[derive(Debug, Clone)]
+struct OpenInterpolation {
+    // `{` opened inside the expression and not closed yet
+    braces: usize,
+    line: u32,
+    column: u32,
+    span: Span,
 }
 
-impl Scanner {
-    pub fn new(source: String) -> Self {
+impl<'a> Scanner<'a> {
+    pub fn new(source: &'a str) -> Self {
         Self {
-            source: source.chars().collect(),
+            source,
             tokens: Vec::new(),
             start: 0,
             current: 0,
             line: 1,
             column: 0,
+            start_line: 1,
+            start_column: 1,
             errors: Vec::new(),
+            interpolations: Vec::new(),
+            pending: None,
+            finished: false,
         }
     }
 
     pub fn scan_tokens(&mut self) {
-        while !self.is_at_end() {
-            // We are at the beginning of the next lexeme.
-            self.start = self.current;
-            Self::scan_token(self);
+        while let Some(result) = self.next() {
+            match result {
+                Ok(token) => self.tokens.push(token),
+                Err(error) => self.errors.push(error),
+            }
         }
-
-        self.tokens.push(Token::new(
-            TokenType::Eof,
-            "".to_string(),
-            None,
-            self.line,
-            self.column,
-        ));
     }
 
     fn is_at_end(&self) -> bool {
         self.current >= self.source.len()
     }
 
-    fn scan_token(&mut self) {
-        let current_char = Self::advance(self);
-        match current_char {
-            '(' => Self::add_token(self, TokenType::LeftParen, None),
-            ')' => Self::add_token(self, TokenType::RightParen, None),
-            '{' => Self::add_token(self, TokenType::LeftBrace, None),
-            '}' => Self::add_token(self, TokenType::RightBrace, None),
-            ',' => Self::add_token(self, TokenType::Comma, None),
-            '.' => Self::add_token(self, TokenType::Dot, None),
-            '-' => Self::add_token(self, TokenType::Minus, None),
-            '+' => Self::add_token(self, TokenType::Plus, None),
-            ';' => Self::add_token(self, TokenType::Semicolon, None),
-            '*' => Self::add_token(self, TokenType::Star, None),
-            '!' => {
-                if Self::advance_peek(self, '=') {
-                    Self::add_token(self, TokenType::BangEqual, None);
+    // None for whitespace and comments, which don't make a token
+    fn scan_token(&mut self) -> Option<Result<Token, ScannerError>> {
+        let current_byte = Self::advance(self);
+        let token_type = match current_byte {
+            b'(' => TokenType::LeftParen,
+            b')' => TokenType::RightParen,
+            b'{' => {
+                if let Some(interpolation) = self.interpolations.last_mut() {
+                    interpolation.braces += 1;
+                }
+                TokenType::LeftBrace
+            }
+            b'}' => match self.interpolations.last_mut() {
+                // the end of the expression, the string carries on
+                Some(interpolation) if interpolation.braces == 0 => {
+                    self.interpolations.pop();
+                    return Self::construct_string(self);
+                }
+                Some(interpolation) => {
+                    interpolation.braces -= 1;
+                    TokenType::RightBrace
+                }
+                None => TokenType::RightBrace,
+            },
+            b',' => TokenType::Comma,
+            b'.' => TokenType::Dot,
+            b'-' => TokenType::Minus,
+            b'+' => TokenType::Plus,
+            b';' => TokenType::Semicolon,
+            b'*' => TokenType::Star,
+            b'!' => {
+                if Self::advance_peek(self, b'=') {
+                    TokenType::BangEqual
                 } else {
-                    Self::add_token(self, TokenType::Bang, None);
+                    TokenType::Bang
                 }
             }
-            '=' => {
-                if Self::advance_peek(self, '=') {
-                    Self::add_token(self, TokenType::EqualEqual, None);
+            b'=' => {
+                if Self::advance_peek(self, b'=') {
+                    TokenType::EqualEqual
                 } else {
-                    Self::add_token(self, TokenType::Equal, None);
+                    TokenType::Equal
                 }
             }
-            '<' => {
-                if Self::advance_peek(self, '=') {
-                    Self::add_token(self, TokenType::LessEqual, None);
+            b'<' => {
+                if Self::advance_peek(self, b'=') {
+                    TokenType::LessEqual
                 } else {
-                    Self::add_token(self, TokenType::Less, None);
+                    TokenType::Less
                 }
             }
-            '>' => {
-                if Self::advance_peek(self, '=') {
-                    Self::add_token(self, TokenType::GreaterEqual, None);
+            b'>' => {
+                if Self::advance_peek(self, b'=') {
+                    TokenType::GreaterEqual
                 } else {
-                    Self::add_token(self, TokenType::Greater, None);
+                    TokenType::Greater
                 }
             }
-            '/' => {
-                if Self::advance_peek(self, '/') {
+            b'/' => {
+                if Self::advance_peek(self, b'/') {
                     // A comment goes until the end of the line.
-                    while self.peek() != '\n' && !self.is_at_end() {
+                    while self.peek() != b'\n' && !self.is_at_end() {
                         Self::advance(self);
                     }
+                    return None;
+                } else if Self::advance_peek(self, b'*') {
+                    return Self::block_comment(self).err().map(Err);
                 } else {
-                    Self::add_token(self, TokenType::Slash, None);
+                    TokenType::Slash
                 }
             }
-            ' ' | '\r' | '\t' => {
+            b' ' | b'\r' | b'\t' => {
                 // Ignore whitespace.
+                return None;
             }
-            '\n' => {
-                self.line += 1;
-                self.column = 0;
-            }
-            '"' => {
-                Self::construct_string(self);
-            }
-            '0'..='9' => {
-                Self::construct_number(self);
-            }
-            'a'..='z' | 'A'..='Z' | '_' => {
-                Self::construct_identifier(self);
+            b'\n' => {
+                // advance already moved to the next line
+                return None;
             }
+            b'"' => return Self::construct_string(self),
+            b'0'..=b'9' => return Some(Ok(Self::construct_number(self))),
+            b'a'..=b'z' | b'A'..=b'Z' | b'_' => return Some(Ok(Self::construct_identifier(self))),
             _ => {
-                self.errors.push(ScannerError {
-                    message: format!("Unexpected character: {}", current_char),
-                    line: self.line,
-                    column: self.column,
-                });
+                // the error covers the whole character, not just its first byte
+                while !self.source.is_char_boundary(self.current) {
+                    Self::advance(self);
+                }
+                return Some(Err(self.error(
+                    codes::UNEXPECTED_CHARACTER,
+                    format!("Unexpected character: {}", self.lexeme()),
+                )));
             }
-        }
+        };
+
+        Some(Ok(Self::make_token(self, token_type, None)))
     }
 
-    fn advance(&mut self) -> char {
-        let current_char = self.source[self.current];
+    fn advance(&mut self) -> u8 {
+        let current_byte = self.source.as_bytes()[self.current];
         self.current += 1;
-        self.column += 1;
-
-        current_char
-    }
 
-    fn advance_peek(&mut self, expected: char) -> bool {
-        if self.is_at_end() {
-            return false;
+        if current_byte == b'\n' {
+            self.line += 1;
+            self.column = 0;
+        } else if !is_continuation_byte(current_byte) {
+            self.column += 1;
         }
 
-        let next_char = self.source[self.current];
-        if next_char != expected {
+        current_byte
+    }
+
+    fn advance_peek(&mut self, expected: u8) -> bool {
+        if self.peek() != expected || self.is_at_end() {
             return false;
         }
 
@@ -154,82 +212,271 @@ impl Scanner {
         true
     }
 
-    fn peek(&mut self) -> char {
-        if self.is_at_end() {
-            return '\0';
-        }
+    fn peek(&self) -> u8 {
+        self.source
+            .as_bytes()
+            .get(self.current)
+            .copied()
+            .unwrap_or(b'\0')
+    }
 
-        self.source[self.current]
+    fn peek_next(&self) -> u8 {
+        self.source
+            .as_bytes()
+            .get(self.current + 1)
+            .copied()
+            .unwrap_or(b'\0')
     }
 
-    fn construct_string(&mut self) {
-        while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
+    // scans from an opening `"` or the `}` ending an interpolation, up to the
+    // closing `"` or the next `${`. The lexeme keeps the escapes as written,
+    // the literal holds the decoded value.
+    fn construct_string(&mut self) -> Option<Result<Token, ScannerError>> {
+        // stays None until an escape shows up, the value is a slice of the lexeme until then
+        let mut decoded: Option<String> = None;
+        let mut segment_start = self.current;
+        let mut invalid_escape = None;
+
+        // newlines inside the string are counted by advance
+        while self.peek() != b'"' && !self.is_at_end() {
+            if self.peek() == b'$' && self.peek_next() == b'{' {
+                return Some(Self::interpolation_segment(
+                    self,
+                    decoded,
+                    segment_start,
+                    invalid_escape,
+                ));
             }
-            Self::advance(self);
+            if self.peek() != b'\\' {
+                Self::advance(self);
+                continue;
+            }
+
+            let decoded = decoded.get_or_insert_with(String::new);
+            decoded.push_str(&self.source[segment_start..self.current]);
+            match Self::escape_sequence(self) {
+                Ok(character) => decoded.push(character),
+                // only the first bad escape is reported, the string is still scanned to its end
+                Err(error) => {
+                    invalid_escape.get_or_insert(error);
+                }
+            }
+            segment_start = self.current;
         }
 
         // Unterminated string.
         if self.is_at_end() {
-            self.errors.push(ScannerError {
-                message: "Unterminated string.".to_string(),
-                line: self.line,
-                column: self.column,
-            });
-            return;
+            // inside an interpolation this string was opened by the `"` meant
+            // to close the outer one, the `${` left open is the error to report
+            if !self.interpolations.is_empty() {
+                return None;
+            }
+            return Some(Err(self.error(
+                codes::UNTERMINATED_STRING,
+                "Unterminated string.".to_string(),
+            )));
         }
 
         // The closing ".
         // We need to advance one more time to consume the closing ".
 
         Self::advance(self);
-        let value: String = self.source[self.start + 1..self.current - 1]
-            .iter()
-            .collect();
+        if let Some(error) = invalid_escape {
+            return Some(Err(error));
+        }
 
-        Self::add_token(self, TokenType::String, Some(Literal::String(value)));
+        let rest = &self.source[segment_start..self.current - 1];
+        let value = Self::segment_value(decoded, rest);
+        Some(Ok(Self::make_token(
+            self,
+            TokenType::String,
+            Some(Literal::String(value)),
+        )))
     }
 
-    fn construct_number(&mut self) {
-        while self.peek().is_numeric() {
+    // ends a string segment at the `${` under `current`
+    fn interpolation_segment(
+        &mut self,
+        decoded: Option<String>,
+        segment_start: usize,
+        invalid_escape: Option<ScannerError>,
+    ) -> Result<Token, ScannerError> {
+        let rest = &self.source[segment_start..self.current];
+        let (line, column, start) = (self.line, self.column + 1, self.current);
+        Self::advance(self);
+        Self::advance(self);
+
+        // the `}` ahead has to resume the string
+        self.interpolations.push(OpenInterpolation {
+            braces: 0,
+            line,
+            column,
+            span: Span::new(start, self.current),
+        });
+
+        let value = Self::segment_value(decoded, rest);
+        let token = Self::make_token(self, TokenType::Interpolation, Some(Literal::String(value)));
+        match invalid_escape {
+            // the parser still needs the segment to keep its place in the string
+            Some(error) => {
+                self.pending = Some(token);
+                Err(error)
+            }
+            None => Ok(token),
+        }
+    }
+
+    // the value of a string segment, `rest` is what follows the last escape
+    fn segment_value(decoded: Option<String>, rest: &str) -> Symbol {
+        match decoded {
+            Some(mut decoded) => {
+                decoded.push_str(rest);
+                Symbol::from(decoded)
+            }
+            None => Symbol::intern(rest),
+        }
+    }
+
+    // decodes the escape sequence starting at the backslash under `current`,
+    // errors point at the backslash
+    fn escape_sequence(&mut self) -> Result<char, ScannerError> {
+        let (start, line, column) = (self.current, self.line, self.column + 1);
+        Self::advance(self);
+
+        let character = match self.peek() {
+            b'u' => {
+                Self::advance(self);
+                Self::unicode_escape(self)
+            }
+            // the string ends right after the backslash
+            _ if self.is_at_end() => None,
+            escaped => {
+                Self::advance(self);
+                while !self.source.is_char_boundary(self.current) {
+                    Self::advance(self);
+                }
+                match escaped {
+                    b'n' => Some('\n'),
+                    b't' => Some('\t'),
+                    b'r' => Some('\r'),
+                    b'\\' => Some('\\'),
+                    b'"' => Some('"'),
+                    b'0' => Some('\0'),
+                    // so a literal `${` can be written as `\${`
+                    b'$' => Some('$'),
+                    _ => None,
+                }
+            }
+        };
+
+        match character {
+            Some(character) => Ok(character),
+            None => Err(ScannerError {
+                code: codes::INVALID_ESCAPE,
+                message: format!(
+                    "Invalid escape sequence: {}",
+                    &self.source[start..self.current]
+                ),
+                line,
+                start_line: line,
+                column,
+                span: Span::new(start, self.current),
+            }),
+        }
+    }
+    // `{`, one to six hex digits and `}`, naming a Unicode scalar value
+    fn unicode_escape(&mut self) -> Option<char> {
+        if self.peek() != b'{' {
+            return None;
+        }
+        Self::advance(self);
+
+        let digits_start = self.current;
+        while self.peek().is_ascii_hexdigit() {
+            Self::advance(self);
+        }
+        let digits = &self.source[digits_start..self.current];
+
+        if self.peek() != b'}' {
+            return None;
+        }
+        Self::advance(self);
+
+        if digits.is_empty() || digits.len() > 6 {
+            return None;
+        }
+        u32::from_str_radix(digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+    }
+
+    // skips a comment whose `/*` has been consumed, comments nest so every
+    // `/*` inside needs its own `*/`
+    fn block_comment(&mut self) -> Result<(), ScannerError> {
+        let mut depth = 1;
+
+        while depth > 0 {
+            if self.is_at_end() {
+                return Err(ScannerError {
+                    code: codes::UNTERMINATED_BLOCK_COMMENT,
+                    message: "Unterminated block comment.".to_string(),
+                    // the error points at the opening `/*`, not at the end of the file
+                    line: self.start_line,
+                    start_line: self.start_line,
+                    column: self.start_column,
+                    span: Span::new(self.start, self.start + 2),
+                });
+            }
+
+            // newlines inside the comment are counted by advance
+            match Self::advance(self) {
+                b'/' if Self::advance_peek(self, b'*') => depth += 1,
+                b'*' if Self::advance_peek(self, b'/') => depth -= 1,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn construct_number(&mut self) -> Token {
+        while self.peek().is_ascii_digit() {
             Self::advance(self);
         }
 
         // Look for a fractional part.
-        if self.peek() == '.' && self.peek_next().is_numeric() {
+        if self.peek() == b'.' && self.peek_next().is_ascii_digit() {
             // Consume the "."
             Self::advance(self);
 
-            while self.peek().is_numeric() {
+            while self.peek().is_ascii_digit() {
                 Self::advance(self);
             }
         }
 
-        let value: f64 = self.source[self.start..self.current]
-            .iter()
-            .collect::<String>()
+        let value: f64 = self
+            .lexeme()
             .parse()
-            .unwrap();
+            .expect("digits with an optional fraction are a valid f64");
 
-        Self::add_token(self, TokenType::Number, Some(Literal::Number(value)));
+        Self::make_token(self, TokenType::Number, Some(Literal::Number(value)))
     }
 
-    fn peek_next(&mut self) -> char {
-        if self.current + 1 >= self.source.len() {
-            return '\0';
-        }
-
-        self.source[self.current + 1]
-    }
-
-    fn construct_identifier(&mut self) {
-        while self.peek().is_alphanumeric() || self.peek() == '_' {
-            Self::advance(self);
+    fn construct_identifier(&mut self) -> Token {
+        loop {
+            let next = self.peek();
+            if next.is_ascii_alphanumeric() || next == b'_' {
+                Self::advance(self);
+            } else if !next.is_ascii() && self.peek_char().is_alphanumeric() {
+                // identifiers may continue with any alphanumeric character
+                for _ in 0..self.peek_char().len_utf8() {
+                    Self::advance(self);
+                }
+            } else {
+                break;
+            }
         }
 
-        let text: String = self.source[self.start..self.current].iter().collect();
-        let token_type = match text.as_str() {
+        let token_type = match self.lexeme() {
             "and" => TokenType::And,
             "class" => TokenType::Class,
             "else" => TokenType::Else,
@@ -257,26 +504,108 @@ impl Scanner {
             _ => {}
         }
 
-        Self::add_token(self, token_type, literal);
+        Self::make_token(self, token_type, literal)
+    }
+
+    // the character starting at `current`, only called on a character boundary
+    fn peek_char(&self) -> char {
+        self.source[self.current..].chars().next().unwrap_or('\0')
+    }
+
+    fn lexeme(&self) -> &'a str {
+        &self.source[self.start..self.current]
     }
 
-    fn add_token(&mut self, token_type: TokenType, literal: Option<Literal>) {
-        let text = self.source[self.start..self.current].iter().collect();
-        self.tokens.push(Token::new(
+    fn make_token(&self, token_type: TokenType, literal: Option<Literal>) -> Token {
+        Token::new(
             token_type,
-            text,
+            Symbol::intern(self.lexeme()),
             literal,
             self.line,
-            self.column,
-        ));
+            self.start_line,
+            self.start_column,
+            self.current_span(),
+        )
+    }
+
+    fn error(&self, code: &'static str, message: String) -> ScannerError {
+        ScannerError {
+            code,
+            message,
+            line: self.line,
+            start_line: self.start_line,
+            column: self.start_column,
+            span: self.current_span(),
+        }
+    }
+
+    fn current_span(&self) -> Span {
+        Span::new(self.start, self.current)
     }
 }
 
+impl Iterator for Scanner<'_> {
+    type Item = Result<Token, ScannerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(token) = self.pending.take() {
+            return Some(Ok(token));
+        }
+
+        while !self.is_at_end() {
+            // We are at the beginning of the next lexeme.
+            self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.column + 1;
+            if let Some(result) = Self::scan_token(self) {
+                return Some(result);
+            }
+        }
+
+        // the error points at the outermost `${` left open
+        if let Some(interpolation) = self.interpolations.first().cloned() {
+            self.interpolations.clear();
+            return Some(Err(ScannerError {
+                code: codes::UNTERMINATED_INTERPOLATION,
+                message: "Unterminated interpolation.".to_string(),
+                line: interpolation.line,
+                start_line: interpolation.line,
+                column: interpolation.column,
+                span: interpolation.span,
+            }));
+        }
+
+        if self.finished {
+            return None;
+        }
+        self.finished = true;
+        Some(Ok(Token::new(
+            TokenType::Eof,
+            Symbol::intern(""),
+            None,
+            self.line,
+            self.line,
+            self.column + 1,
+            Span::new(self.current, self.current),
+        )))
+    }
+}
+
+// the bytes after the first in a multi-byte UTF-8 character
+fn is_continuation_byte(byte: u8) -> bool {
+    byte & 0b1100_0000 == 0b1000_0000
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct ScannerError {
+    pub code: &'static str,
     pub message: String,
+    // like a token's, `line` is where the error ends and `start_line` and
+    // `column` where it starts
     pub line: u32,
+    pub start_line: u32,
     pub column: u32,
+    pub span: Span,
 }
 
 impl ScannerError {}
@@ -288,13 +617,15 @@ impl std::fmt::Display for ScannerError {
 }
 
 #[cfg(test)]
+// the starter tests build their sources with `format!`
+#[allow(clippy::useless_format)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_scanner_codecrafter_testcase() {
         let source = "(()".to_string();
-        let mut scanner = Scanner::new(source);
+        let mut scanner = Scanner::new(&source);
 
         scanner.scan_tokens();
         assert_eq!(scanner.tokens.len(), 4);
@@ -307,7 +638,7 @@ mod tests {
     #[test]
     fn test_scan_tokens() {
         let source = "(){},.-+;*".to_string();
-        let mut scanner = Scanner::new(source);
+        let mut scanner = Scanner::new(&source);
         scanner.scan_tokens();
 
         assert_eq!(scanner.tokens.len(), 11);
@@ -327,17 +658,216 @@ mod tests {
     #[test]
     fn test_scan_tokens_for_string() {
         let source = format!("\"Coolstorm\"");
-        let mut scanner = Scanner::new(source);
+        let mut scanner = Scanner::new(&source);
+        scanner.scan_tokens();
+
+        assert_eq!(scanner.tokens.len(), 2);
+        assert_eq!(scanner.tokens[0].token_type, TokenType::String);
+    }
+
+    #[test]
+    fn test_scan_tokens_reports_unterminated_string_where_it_starts_and_ends() {
+        let mut scanner = Scanner::new("print\n  \"a\nb");
+        scanner.scan_tokens();
+
+        let error = &scanner.errors[0];
+        assert_eq!(error.message, "Unterminated string.");
+        // `[line N]` names the last line, as jlox does
+        assert_eq!(error.line, 3);
+        assert_eq!((error.start_line, error.column), (2, 3));
+    }
+
+    #[test]
+    fn test_scan_tokens_spans_across_lines() {
+        let source = "var s = \"é\nb\";\n  x = 1;".to_string();
+        let mut scanner = Scanner::new(&source);
+        scanner.scan_tokens();
+
+        let string = &scanner.tokens[3];
+        assert_eq!(string.span, Span::new(8, 14));
+        assert_eq!(string.line, 2);
+        assert_eq!((string.start_line, string.column), (1, 9));
+
+        let identifier = &scanner.tokens[5];
+        assert_eq!(identifier.lexeme, "x");
+        assert_eq!(identifier.span, Span::new(18, 19));
+        assert_eq!((identifier.line, identifier.column), (3, 3));
+
+        let eof = scanner.tokens.last().unwrap();
+        assert_eq!(eof.span, Span::new(24, 24));
+    }
+
+    #[test]
+    fn test_scan_tokens_with_multibyte_characters() {
+        let source = "é£ naïve".to_string();
+        let mut scanner = Scanner::new(&source);
+        scanner.scan_tokens();
+
+        let messages: Vec<_> = scanner.errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            ["Unexpected character: é", "Unexpected character: £"]
+        );
+        assert_eq!(scanner.errors[1].span, Span::new(2, 4));
+        assert_eq!(scanner.errors[1].column, 2);
+
+        let identifier = &scanner.tokens[0];
+        assert_eq!(identifier.lexeme, "naïve");
+        assert_eq!((identifier.column, identifier.span), (4, Span::new(5, 11)));
+    }
+
+    #[test]
+    fn test_scan_tokens_decodes_escape_sequences() {
+        let source = r#""a\"b\\c\n\t\r\0 \u{48}\u{1F600}é""#.to_string();
+        let mut scanner = Scanner::new(&source);
+        scanner.scan_tokens();
+
+        assert!(scanner.errors.is_empty());
+        let string = &scanner.tokens[0];
+        assert_eq!(string.lexeme, source.as_str());
+        assert_eq!(
+            string.literal,
+            Some(Literal::String(Symbol::intern("a\"b\\c\n\t\r\0 H😀é")))
+        );
+    }
+
+    #[test]
+    fn test_scan_tokens_reports_invalid_escapes_at_the_backslash() {
+        for (source, escape, column) in [
+            (r#"print "ok\q";"#, r"\q", 10),
+            (r#"print "\u{110000}";"#, r"\u{110000}", 8),
+            (r#"print "\u{}";"#, r"\u{}", 8),
+            (r#"print "\u{D800}\q";"#, r"\u{D800}", 8),
+            ("print \"é\\u41\";", r"\u", 9),
+        ] {
+            let mut scanner = Scanner::new(source);
+            scanner.scan_tokens();
+
+            let messages: Vec<_> = scanner.errors.iter().map(|e| e.message.as_str()).collect();
+            let expected = format!("Invalid escape sequence: {}", escape);
+            assert_eq!(messages, [expected.as_str()], "{}", source);
+            assert_eq!(scanner.errors[0].column, column, "{}", source);
+
+            let span = scanner.errors[0].span;
+            assert_eq!(&source[span.start..span.end], escape);
+            // the string is skipped, scanning carries on after it
+            assert_eq!(scanner.tokens[1].token_type, TokenType::Semicolon);
+        }
+
+        let mut scanner = Scanner::new("\"trailing\\\"");
+        scanner.scan_tokens();
+        assert_eq!(scanner.errors[0].message, "Unterminated string.");
+    }
+
+    #[test]
+    fn test_scan_tokens_splits_interpolated_strings() {
+        let source = r#""a ${ {x} + "b${y}" } c""#.to_string();
+        let mut scanner = Scanner::new(&source);
         scanner.scan_tokens();
 
+        assert!(scanner.errors.is_empty());
+        let tokens: Vec<_> = scanner
+            .tokens
+            .iter()
+            .map(|token| (token.token_type, token.lexeme.to_string()))
+            .collect();
+        assert_eq!(
+            tokens,
+            [
+                (TokenType::Interpolation, "\"a ${".to_string()),
+                (TokenType::LeftBrace, "{".to_string()),
+                (TokenType::Identifier, "x".to_string()),
+                (TokenType::RightBrace, "}".to_string()),
+                (TokenType::Plus, "+".to_string()),
+                (TokenType::Interpolation, "\"b${".to_string()),
+                (TokenType::Identifier, "y".to_string()),
+                (TokenType::String, "}\"".to_string()),
+                (TokenType::String, "} c\"".to_string()),
+                (TokenType::Eof, "".to_string()),
+            ]
+        );
+        assert_eq!(
+            scanner.tokens[8].literal,
+            Some(Literal::String(Symbol::intern(" c")))
+        );
+    }
+
+    #[test]
+    fn test_scan_tokens_keeps_escaped_interpolations_literal() {
+        let mut scanner = Scanner::new(r#""cost: \${price} $5""#);
+        scanner.scan_tokens();
+
+        assert!(scanner.errors.is_empty());
         assert_eq!(scanner.tokens.len(), 2);
         assert_eq!(scanner.tokens[0].token_type, TokenType::String);
+        assert_eq!(
+            scanner.tokens[0].literal,
+            Some(Literal::String(Symbol::intern("cost: ${price} $5")))
+        );
+    }
+
+    #[test]
+    fn test_scan_tokens_reports_unterminated_interpolation_at_the_opening() {
+        let mut scanner = Scanner::new("print \"a ${b + \"c ${d\n+ 1");
+        scanner.scan_tokens();
+
+        let messages: Vec<_> = scanner.errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, ["Unterminated interpolation."]);
+        let error = &scanner.errors[0];
+        assert_eq!((error.line, error.column), (1, 10));
+        assert_eq!(error.span, Span::new(9, 11));
+
+        // a bad escape still leaves the segment in place for the parser
+        let mut scanner = Scanner::new(r#""\q ${1} b""#);
+        scanner.scan_tokens();
+        assert_eq!(scanner.errors.len(), 1);
+        assert_eq!(scanner.tokens[0].token_type, TokenType::Interpolation);
+        assert_eq!(scanner.tokens[2].token_type, TokenType::String);
+    }
+
+    #[test]
+    fn test_scan_tokens_skips_nested_block_comments() {
+        let source = "1 /* a /* b\n*/ c */ / 2 /**/ /*/ x */\n3 */".to_string();
+        let mut scanner = Scanner::new(&source);
+        scanner.scan_tokens();
+
+        assert!(scanner.errors.is_empty());
+        let tokens: Vec<_> = scanner
+            .tokens
+            .iter()
+            .map(|token| (token.token_type, token.line))
+            .collect();
+        assert_eq!(
+            tokens,
+            [
+                (TokenType::Number, 1),
+                (TokenType::Slash, 2),
+                (TokenType::Number, 2),
+                (TokenType::Number, 3),
+                (TokenType::Star, 3),
+                (TokenType::Slash, 3),
+                (TokenType::Eof, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_tokens_reports_unterminated_block_comment_where_it_starts() {
+        let mut scanner = Scanner::new("print 1;\n  /* a /* b */\n\nprint 2;");
+        scanner.scan_tokens();
+
+        let messages: Vec<_> = scanner.errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, ["Unterminated block comment."]);
+        let error = &scanner.errors[0];
+        assert_eq!((error.line, error.column), (2, 3));
+        assert_eq!(error.span, Span::new(11, 13));
+        assert_eq!(scanner.tokens.last().unwrap().line, 4);
     }
 
     #[test]
     fn test_scan_tokens_for_number() {
         let source = format!("123.45");
-        let mut scanner = Scanner::new(source);
+        let mut scanner = Scanner::new(&source);
         scanner.scan_tokens();
 
         assert_eq!(scanner.tokens.len(), 2);
@@ -0,0 +1,655 @@
+use std::rc::Rc;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::chunk::{Chunk, Constant, Function, LineRun, OpCode};
+use crate::domain::Span;
+
+/*
+    Compiled scripts can be saved to a `.loxc` file and run later without the
+    source. The file is laid out as
+
+        magic     "LOXC"
+        version   u16
+        length    u32, of the payload
+        checksum  u32, CRC-32 of the payload
+        payload   the script function
+
+    and a function is its name, arity, upvalue count and chunk: the code, the
+    constants (numbers, strings and nested functions, each behind a one byte
+    tag), the run-length encoded line table and the span table. Integers are
+    big endian, strings are a u32 length followed by UTF-8.
+
+    Loading checks the header and checksum, then verifies the bytecode itself
+    so a damaged file is rejected with an error instead of crashing the VM.
+*/
+
+pub const MAGIC: &[u8; 4] = b"LOXC";
+// version 2 added OP_STRINGIFY, version 1 files are still valid
+pub const VERSION: u16 = 2;
+const OLDEST_VERSION: u16 = 1;
+
+const HEADER_LEN: usize = 4 + 2 + 4 + 4;
+
+const TAG_NUMBER: u8 = 0;
+const TAG_STRING: u8 = 1;
+const TAG_FUNCTION: u8 = 2;
+
+// deeper nested functions than this are rejected rather than overflowing the stack while reading
+const MAX_NESTING: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    NotCompiled,
+    UnsupportedVersion(u16),
+    ChecksumMismatch,
+    Truncated,
+    Invalid(String),
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::NotCompiled => write!(f, "Not a compiled Lox file."),
+            LoadError::UnsupportedVersion(version) => write!(
+                f,
+                "Compiled with format version {}, but only versions {} to {} are supported. Recompile the script.",
+                version, OLDEST_VERSION, VERSION
+            ),
+            LoadError::ChecksumMismatch => {
+                write!(f, "Checksum mismatch, the file is corrupted.")
+            }
+            LoadError::Truncated => write!(f, "Unexpected end of file, the file is truncated."),
+            LoadError::Invalid(message) => write!(f, "Invalid bytecode: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+pub fn serialize(function: &Function) -> Bytes {
+    let mut payload = BytesMut::new();
+    write_function(&mut payload, function);
+
+    let mut out = BytesMut::with_capacity(HEADER_LEN + payload.len());
+    out.put_slice(MAGIC);
+    out.put_u16(VERSION);
+    out.put_u32(payload.len() as u32);
+    out.put_u32(crc32(&payload));
+    out.put_slice(&payload);
+    out.freeze()
+}
+
+pub fn deserialize(data: &[u8]) -> Result<Rc<Function>, LoadError> {
+    let mut data = data;
+    if data.len() < MAGIC.len() || &data[..MAGIC.len()] != MAGIC {
+        return Err(LoadError::NotCompiled);
+    }
+    data.advance(MAGIC.len());
+
+    let version = read_u16(&mut data)?;
+    if !(OLDEST_VERSION..=VERSION).contains(&version) {
+        return Err(LoadError::UnsupportedVersion(version));
+    }
+
+    let length = read_u32(&mut data)? as usize;
+    let checksum = read_u32(&mut data)?;
+    if data.len() < length {
+        return Err(LoadError::Truncated);
+    }
+    if data.len() > length {
+        return Err(LoadError::Invalid(
+            "trailing bytes after the script".to_string(),
+        ));
+    }
+    if crc32(data) != checksum {
+        return Err(LoadError::ChecksumMismatch);
+    }
+
+    let function = read_function(&mut data, 0)?;
+    // the script is run without arguments or a closure around it
+    if function.arity != 0 || function.upvalue_count != 0 {
+        return Err(LoadError::Invalid(
+            "the script has parameters or upvalues".to_string(),
+        ));
+    }
+    if data.has_remaining() {
+        return Err(LoadError::Invalid(
+            "trailing bytes after the script".to_string(),
+        ));
+    }
+    Ok(Rc::new(function))
+}
+
+// helpers to write
+
+fn write_function(out: &mut BytesMut, function: &Function) {
+    write_string(out, &function.name);
+    out.put_u32(function.arity as u32);
+    out.put_u32(function.upvalue_count as u32);
+    write_chunk(out, &function.chunk);
+}
+
+fn write_chunk(out: &mut BytesMut, chunk: &Chunk) {
+    out.put_u32(chunk.code.len() as u32);
+    out.put_slice(&chunk.code);
+
+    out.put_u32(chunk.constants.len() as u32);
+    for constant in &chunk.constants {
+        match constant {
+            Constant::Number(number) => {
+                out.put_u8(TAG_NUMBER);
+                out.put_f64(*number);
+            }
+            Constant::String(string) => {
+                out.put_u8(TAG_STRING);
+                write_string(out, string);
+            }
+            Constant::Function(function) => {
+                out.put_u8(TAG_FUNCTION);
+                write_function(out, function);
+            }
+        }
+    }
+
+    out.put_u32(chunk.lines.len() as u32);
+    for run in &chunk.lines {
+        out.put_u32(run.line);
+        out.put_u32(run.count as u32);
+    }
+
+    out.put_u32(chunk.spans.len() as u32);
+    for (offset, span) in &chunk.spans {
+        out.put_u32(*offset as u32);
+        out.put_u32(span.start as u32);
+        out.put_u32(span.end as u32);
+    }
+}
+
+fn write_string(out: &mut BytesMut, string: &str) {
+    out.put_u32(string.len() as u32);
+    out.put_slice(string.as_bytes());
+}
+
+// helpers to read, every read checks there are enough bytes left first
+
+fn read_function(data: &mut &[u8], nesting: usize) -> Result<Function, LoadError> {
+    if nesting > MAX_NESTING {
+        return Err(LoadError::Invalid(
+            "functions are nested too deeply".to_string(),
+        ));
+    }
+    let name = read_string(data)?;
+    let arity = read_u32(data)? as usize;
+    let upvalue_count = read_u32(data)? as usize;
+    let chunk = read_chunk(data, nesting)?;
+
+    let function = Function {
+        name,
+        arity,
+        upvalue_count,
+        chunk,
+    };
+    verify(&function)?;
+    Ok(function)
+}
+
+fn read_chunk(data: &mut &[u8], nesting: usize) -> Result<Chunk, LoadError> {
+    let mut chunk = Chunk::new();
+
+    let length = read_u32(data)? as usize;
+    chunk.code = read_bytes(data, length)?.to_vec();
+
+    let count = read_u32(data)?;
+    for _ in 0..count {
+        let constant = match read_u8(data)? {
+            TAG_NUMBER => {
+                ensure(data, 8)?;
+                Constant::Number(data.get_f64())
+            }
+            TAG_STRING => Constant::String(read_string(data)?),
+            TAG_FUNCTION => Constant::Function(Rc::new(read_function(data, nesting + 1)?)),
+            tag => return Err(LoadError::Invalid(format!("unknown constant tag {}", tag))),
+        };
+        chunk.constants.push(constant);
+    }
+
+    let count = read_u32(data)?;
+    for _ in 0..count {
+        let line = read_u32(data)?;
+        let count = read_u32(data)? as usize;
+        chunk.lines.push(LineRun { line, count });
+    }
+
+    let count = read_u32(data)?;
+    for _ in 0..count {
+        let offset = read_u32(data)? as usize;
+        let start = read_u32(data)? as usize;
+        let end = read_u32(data)? as usize;
+        chunk.spans.push((offset, Span::new(start, end)));
+    }
+
+    Ok(chunk)
+}
+
+fn ensure(data: &[u8], length: usize) -> Result<(), LoadError> {
+    if data.remaining() < length {
+        return Err(LoadError::Truncated);
+    }
+    Ok(())
+}
+
+fn read_u8(data: &mut &[u8]) -> Result<u8, LoadError> {
+    ensure(data, 1)?;
+    Ok(data.get_u8())
+}
+
+fn read_u16(data: &mut &[u8]) -> Result<u16, LoadError> {
+    ensure(data, 2)?;
+    Ok(data.get_u16())
+}
+
+fn read_u32(data: &mut &[u8]) -> Result<u32, LoadError> {
+    ensure(data, 4)?;
+    Ok(data.get_u32())
+}
+
+fn read_bytes<'a>(data: &mut &'a [u8], length: usize) -> Result<&'a [u8], LoadError> {
+    ensure(data, length)?;
+    let (bytes, rest) = data.split_at(length);
+    *data = rest;
+    Ok(bytes)
+}
+
+fn read_string(data: &mut &[u8]) -> Result<String, LoadError> {
+    let length = read_u32(data)? as usize;
+    let bytes = read_bytes(data, length)?;
+    String::from_utf8(bytes.to_vec())
+        .map_err(|_| LoadError::Invalid("a string is not valid UTF-8".to_string()))
+}
+
+// checks every instruction is complete and its operands point at something
+// of the right kind, then follows every path through the code to check the
+// stack depth, so running the function can't read past the code, the
+// constants, the upvalues or the stack. What kind of value is on the stack,
+// and whether a captured variable is still on it, is only known when running,
+// the VM checks those and reports invalid bytecode.
+fn verify(function: &Function) -> Result<(), LoadError> {
+    let chunk = &function.chunk;
+    let invalid = |message: String| Err(LoadError::Invalid(format!("{} in {}", message, function)));
+
+    if chunk.lines.iter().map(|run| run.count).sum::<usize>() != chunk.code.len() {
+        return invalid("the line table doesn't cover the code".to_string());
+    }
+
+    // the operand checks that don't depend on the stack, and where each instruction starts
+    let mut starts = vec![false; chunk.code.len()];
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        starts[offset] = true;
+        let byte = chunk.code[offset];
+        let Ok(op) = OpCode::try_from(byte) else {
+            return invalid(format!("unknown opcode {} at {}", byte, offset));
+        };
+
+        if op == OpCode::Closure && offset + 3 <= chunk.code.len() {
+            let index = chunk.read_u16(offset + 1) as usize;
+            if !matches!(chunk.constants.get(index), Some(Constant::Function(_))) {
+                return invalid(format!("bad function constant {} at {}", index, offset));
+            }
+        }
+
+        let next = offset + instruction_len(chunk, op, offset);
+        if next > chunk.code.len() {
+            return invalid(format!("incomplete instruction at {}", offset));
+        }
+
+        match op {
+            OpCode::Constant => {
+                let index = chunk.read_u16(offset + 1) as usize;
+                if matches!(
+                    chunk.constants.get(index),
+                    None | Some(Constant::Function(_))
+                ) {
+                    return invalid(format!("bad constant {} at {}", index, offset));
+                }
+            }
+            OpCode::GetGlobal
+            | OpCode::DefineGlobal
+            | OpCode::SetGlobal
+            | OpCode::GetProperty
+            | OpCode::SetProperty
+            | OpCode::GetSuper
+            | OpCode::Class
+            | OpCode::Method => {
+                let index = chunk.read_u16(offset + 1) as usize;
+                if !matches!(chunk.constants.get(index), Some(Constant::String(_))) {
+                    return invalid(format!("bad name constant {} at {}", index, offset));
+                }
+            }
+            OpCode::GetUpvalue | OpCode::SetUpvalue
+                if chunk.code[offset + 1] as usize >= function.upvalue_count =>
+            {
+                return invalid(format!("bad upvalue at {}", offset));
+            }
+            OpCode::Closure => {
+                // each upvalue is an is_local flag and an index, locals are checked with the stack
+                for upvalue in chunk.code[offset + 3..next].chunks(2) {
+                    let bad = match upvalue[0] {
+                        0 => upvalue[1] as usize >= function.upvalue_count,
+                        1 => false,
+                        _ => true,
+                    };
+                    if bad {
+                        return invalid(format!("bad captured upvalue at {}", offset));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        offset = next;
+    }
+
+    // the stack depth before each instruction, relative to the frame, which
+    // starts with the callee and its arguments
+    let mut depths: Vec<Option<usize>> = vec![None; chunk.code.len()];
+    let mut pending = vec![(0, function.arity + 1)];
+    while let Some((offset, depth)) = pending.pop() {
+        if offset >= chunk.code.len() {
+            return invalid("the code doesn't end with a return".to_string());
+        }
+        if !starts[offset] {
+            return invalid(format!(
+                "jump into the middle of an instruction to {}",
+                offset
+            ));
+        }
+        match depths[offset] {
+            Some(seen) if seen == depth => continue,
+            Some(_) => return invalid(format!("inconsistent stack depth at {}", offset)),
+            None => depths[offset] = Some(depth),
+        }
+
+        let op = OpCode::try_from(chunk.code[offset]).expect("checked above");
+        let next = offset + instruction_len(chunk, op, offset);
+        let (pops, pushes) = match op {
+            OpCode::Constant
+            | OpCode::Nil
+            | OpCode::True
+            | OpCode::False
+            | OpCode::GetGlobal
+            | OpCode::GetUpvalue
+            | OpCode::Class => (0, 1),
+            OpCode::Pop | OpCode::DefineGlobal | OpCode::Print | OpCode::CloseUpvalue => (1, 0),
+            OpCode::SetGlobal
+            | OpCode::SetUpvalue
+            | OpCode::GetProperty
+            | OpCode::Stringify
+            | OpCode::Not
+            | OpCode::Negate
+            | OpCode::JumpIfFalse
+            | OpCode::Return => (1, 1),
+            OpCode::SetProperty
+            | OpCode::GetSuper
+            | OpCode::Equal
+            | OpCode::Greater
+            | OpCode::GreaterEqual
+            | OpCode::Less
+            | OpCode::LessEqual
+            | OpCode::Add
+            | OpCode::Subtract
+            | OpCode::Multiply
+            | OpCode::Divide
+            | OpCode::Inherit
+            | OpCode::Method => (2, 1),
+            OpCode::Jump | OpCode::Loop => (0, 0),
+            OpCode::GetLocal => (0, 1),
+            OpCode::SetLocal => (1, 1),
+            // the callee and its arguments are replaced by the result
+            OpCode::Call => (chunk.code[offset + 1] as usize + 1, 1),
+            OpCode::Closure => (0, 1),
+        };
+        if depth < pops {
+            return invalid(format!("stack underflow at {}", offset));
+        }
+
+        match op {
+            OpCode::GetLocal | OpCode::SetLocal if chunk.code[offset + 1] as usize >= depth => {
+                return invalid(format!("bad local slot at {}", offset));
+            }
+            OpCode::Closure => {
+                let captures_missing_local = chunk.code[offset + 3..next]
+                    .chunks(2)
+                    .any(|upvalue| upvalue[0] == 1 && upvalue[1] as usize >= depth);
+                if captures_missing_local {
+                    return invalid(format!("bad captured local at {}", offset));
+                }
+            }
+            _ => {}
+        }
+
+        let depth = depth - pops + pushes;
+        let jump = || chunk.read_u16(offset + 1) as usize;
+        let target = match op {
+            OpCode::Jump | OpCode::JumpIfFalse => Some(next + jump()),
+            OpCode::Loop => next.checked_sub(jump()),
+            _ => None,
+        };
+        if matches!(op, OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop)
+            && target.is_none_or(|target| target >= chunk.code.len())
+        {
+            return invalid(format!("jump out of the code at {}", offset));
+        }
+
+        match op {
+            OpCode::Return => {}
+            OpCode::Jump | OpCode::Loop => pending.extend(target.map(|target| (target, depth))),
+            OpCode::JumpIfFalse => {
+                pending.extend(target.map(|target| (target, depth)));
+                pending.push((next, depth));
+            }
+            _ => pending.push((next, depth)),
+        }
+    }
+
+    Ok(())
+}
+
+// the opcode, its operands, and for a closure the captured upvalues after them
+fn instruction_len(chunk: &Chunk, op: OpCode, offset: usize) -> usize {
+    match op {
+        OpCode::Closure => {
+            let nested = chunk
+                .code
+                .get(offset + 1..offset + 3)
+                .map(|_| chunk.read_u16(offset + 1) as usize)
+                .and_then(|index| chunk.constants.get(index));
+            match nested {
+                Some(Constant::Function(nested)) => 3 + nested.upvalue_count * 2,
+                // the operand is missing, the instruction can't be complete
+                _ => usize::MAX - offset,
+            }
+        }
+        OpCode::Constant
+        | OpCode::GetGlobal
+        | OpCode::DefineGlobal
+        | OpCode::SetGlobal
+        | OpCode::GetProperty
+        | OpCode::SetProperty
+        | OpCode::GetSuper
+        | OpCode::Class
+        | OpCode::Method
+        | OpCode::Jump
+        | OpCode::JumpIfFalse
+        | OpCode::Loop => 3,
+        OpCode::GetLocal
+        | OpCode::SetLocal
+        | OpCode::GetUpvalue
+        | OpCode::SetUpvalue
+        | OpCode::Call => 2,
+        _ => 1,
+    }
+}
+
+// CRC-32 as used by zip and PNG
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn compile(source: &str) -> Rc<Function> {
+        let statements = Parser::new(Scanner::new(source)).parse();
+        Compiler::new(source).compile(&statements)
+    }
+
+    const SOURCE: &str = "
+        class A { init(x) { this.x = x; } get() { return this.x; } }
+        fun counter() { var i = 0; fun next() { i = i + 1; return i; } return next; }
+        var c = counter();
+        while (c() < 3) print \"tick\";
+        print A(1.5).get();";
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let script = compile(SOURCE);
+        let data = serialize(&script);
+        assert_eq!(&data[..4], MAGIC);
+        assert_eq!(deserialize(&data), Ok(script));
+    }
+
+    #[test]
+    fn test_rejects_bad_files() {
+        let data = serialize(&compile(SOURCE)).to_vec();
+
+        assert_eq!(deserialize(b"print 1;"), Err(LoadError::NotCompiled));
+
+        let mut newer = data.clone();
+        newer[5] = 3;
+        assert_eq!(deserialize(&newer), Err(LoadError::UnsupportedVersion(3)));
+
+        let mut older = data.clone();
+        older[5] = 1;
+        assert!(deserialize(&older).is_ok());
+
+        let mut corrupted = data.clone();
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 0xFF;
+        assert_eq!(deserialize(&corrupted), Err(LoadError::ChecksumMismatch));
+
+        assert_eq!(
+            deserialize(&data[..data.len() - 3]),
+            Err(LoadError::Truncated)
+        );
+        assert_eq!(deserialize(&data[..8]), Err(LoadError::Truncated));
+    }
+
+    #[test]
+    fn test_rejects_bytecode_with_a_valid_checksum() {
+        let mut script = Function::clone(&compile("print 1;"));
+        // point the constant instruction past the constant pool
+        script.chunk.code[2] = 9;
+
+        let error = deserialize(&serialize(&script)).unwrap_err();
+        assert_eq!(
+            error,
+            LoadError::Invalid("bad constant 9 at 0 in <script>".to_string())
+        );
+    }
+
+    // a script with the given code, all on line 1
+    fn crafted(code: &[u8]) -> Vec<u8> {
+        let mut script = Function::default();
+        for byte in code {
+            script.chunk.write(*byte, 1, Span::default());
+        }
+        serialize(&script).to_vec()
+    }
+
+    fn invalid(message: &str) -> Result<Rc<Function>, LoadError> {
+        Err(LoadError::Invalid(message.to_string()))
+    }
+
+    #[test]
+    fn test_rejects_stack_underflow() {
+        use OpCode::*;
+
+        let data = crafted(&[Pop as u8, Pop as u8, Pop as u8, Return as u8]);
+        assert_eq!(
+            deserialize(&data),
+            invalid("stack underflow at 1 in <script>")
+        );
+
+        // the path that doesn't jump pops once more than the other
+        let data = crafted(&[
+            True as u8,
+            JumpIfFalse as u8,
+            0,
+            1,
+            Pop as u8,
+            Pop as u8,
+            Return as u8,
+        ]);
+        assert_eq!(
+            deserialize(&data),
+            invalid("stack underflow at 6 in <script>")
+        );
+    }
+
+    #[test]
+    fn test_rejects_bad_jumps_and_slots() {
+        use OpCode::*;
+
+        // over the return to the end of the code
+        let data = crafted(&[Jump as u8, 0, 1, Return as u8]);
+        assert_eq!(
+            deserialize(&data),
+            invalid("jump out of the code at 0 in <script>")
+        );
+
+        let data = crafted(&[Loop as u8, 0, 4, Return as u8]);
+        assert_eq!(
+            deserialize(&data),
+            invalid("jump out of the code at 0 in <script>")
+        );
+
+        // into the operand of the jump itself
+        let data = crafted(&[Loop as u8, 0, 2, Return as u8]);
+        assert_eq!(
+            deserialize(&data),
+            invalid("jump into the middle of an instruction to 1 in <script>")
+        );
+
+        // the script only has its own slot
+        let data = crafted(&[GetLocal as u8, 1, Return as u8]);
+        assert_eq!(
+            deserialize(&data),
+            invalid("bad local slot at 0 in <script>")
+        );
+
+        let data = crafted(&[Nil as u8]);
+        assert_eq!(
+            deserialize(&data),
+            invalid("the code doesn't end with a return in <script>")
+        );
+    }
+}
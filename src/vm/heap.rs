@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+
+use super::object::{BoundMethod, Class, Closure, Function, Instance, Object, Upvalue};
+use super::Value;
+
+/*
+    The Heap owns every object the virtual machine allocates. Objects are
+    stored in an arena and referred to by index, so they can point at each
+    other freely, cycles included.
+
+    Strings are interned: `intern` hands out the same object for equal strings.
+
+    Unreachable objects are reclaimed by a mark-and-sweep collector. The
+    virtual machine marks its roots, `trace` blackens everything reachable from
+    them through a gray worklist, and `sweep` frees whatever stayed white. The
+    intern table only holds its strings weakly, so unused strings are freed
+    too. Freed slots are reused by later allocations.
+
+    Reference - https://craftinginterpreters.com/garbage-collection.html
+*/
+
+// the heap may grow to this many times its live size before the next collection
+const GC_HEAP_GROW_FACTOR: usize = 2;
+const GC_INITIAL_THRESHOLD: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObjRef(usize);
+
+#[derive(Debug)]
+pub struct Heap {
+    objects: Vec<Option<Object>>,
+    strings: HashMap<String, ObjRef>,
+    // slots of freed objects, reused before the arena grows
+    free: Vec<usize>,
+    marks: Vec<bool>,
+    // marked objects whose references haven't been traced yet
+    gray: Vec<ObjRef>,
+    bytes_allocated: usize,
+    next_gc: usize,
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self {
+            objects: Vec::new(),
+            strings: HashMap::new(),
+            free: Vec::new(),
+            marks: Vec::new(),
+            gray: Vec::new(),
+            bytes_allocated: 0,
+            next_gc: GC_INITIAL_THRESHOLD,
+        }
+    }
+}
+
+impl Heap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn alloc(&mut self, object: Object) -> ObjRef {
+        self.bytes_allocated += object.size();
+        match self.free.pop() {
+            Some(index) => {
+                self.objects[index] = Some(object);
+                ObjRef(index)
+            }
+            None => {
+                self.objects.push(Some(object));
+                self.marks.push(false);
+                ObjRef(self.objects.len() - 1)
+            }
+        }
+    }
+
+    pub fn intern(&mut self, string: &str) -> ObjRef {
+        if let Some(reference) = self.strings.get(string) {
+            return *reference;
+        }
+
+        let reference = self.alloc(Object::String(string.to_string()));
+        self.strings.insert(string.to_string(), reference);
+        reference
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, reference: ObjRef) -> &Object {
+        self.objects[reference.0]
+            .as_ref()
+            .expect("reachable objects are never freed")
+    }
+
+    pub fn get_mut(&mut self, reference: ObjRef) -> &mut Object {
+        self.objects[reference.0]
+            .as_mut()
+            .expect("reachable objects are never freed")
+    }
+
+    // the virtual machine collects once allocation passes the threshold
+    pub fn should_collect(&self) -> bool {
+        self.bytes_allocated > self.next_gc
+    }
+
+    pub fn mark_value(&mut self, value: Value) {
+        if let Value::Object(reference) = value {
+            self.mark_object(reference);
+        }
+    }
+
+    // turns a white object gray
+    pub fn mark_object(&mut self, reference: ObjRef) {
+        if !self.marks[reference.0] {
+            self.marks[reference.0] = true;
+            self.gray.push(reference);
+        }
+    }
+
+    // blackens gray objects, marking what they refer to, until none are left
+    pub fn trace(&mut self) {
+        let mut references = Vec::new();
+        while let Some(reference) = self.gray.pop() {
+            let mut values = Vec::new();
+            match self.get(reference) {
+                Object::String(_) | Object::Native(_) | Object::Upvalue(Upvalue::Open(_)) => {}
+                Object::Function(function) => values.extend(function.constants.iter().copied()),
+                Object::Closure(closure) => {
+                    references.push(closure.function);
+                    references.extend(closure.upvalues.iter().copied());
+                }
+                Object::Upvalue(Upvalue::Closed(value)) => values.push(*value),
+                Object::Class(class) => {
+                    references.push(class.name);
+                    references.extend(
+                        class
+                            .methods
+                            .iter()
+                            .flat_map(|(name, method)| [*name, *method]),
+                    );
+                }
+                Object::Instance(instance) => {
+                    references.push(instance.class);
+                    references.extend(instance.fields.keys().copied());
+                    values.extend(instance.fields.values().copied());
+                }
+                Object::BoundMethod(bound) => {
+                    references.push(bound.method);
+                    values.push(bound.receiver);
+                }
+            }
+
+            for reference in references.drain(..) {
+                self.mark_object(reference);
+            }
+            for value in values {
+                self.mark_value(value);
+            }
+        }
+    }
+
+    // frees every unmarked object and clears the marks for the next collection
+    pub fn sweep(&mut self) {
+        let marks = &self.marks;
+        self.strings.retain(|_, reference| marks[reference.0]);
+
+        self.bytes_allocated = 0;
+        for (index, slot) in self.objects.iter_mut().enumerate() {
+            let Some(object) = slot else {
+                continue;
+            };
+            if self.marks[index] {
+                self.marks[index] = false;
+                self.bytes_allocated += object.size();
+            } else {
+                *slot = None;
+                self.free.push(index);
+            }
+        }
+
+        self.next_gc = (self.bytes_allocated * GC_HEAP_GROW_FACTOR).max(GC_INITIAL_THRESHOLD);
+    }
+
+    // typed accessors, the compiler guarantees which kind of object each
+    // instruction finds, so a mismatch is a bug in the virtual machine
+
+    pub fn string(&self, reference: ObjRef) -> &str {
+        match self.get(reference) {
+            Object::String(string) => string,
+            object => unreachable!("expected a string, found {:?}", object),
+        }
+    }
+
+    pub fn function(&self, reference: ObjRef) -> &Function {
+        match self.get(reference) {
+            Object::Function(function) => function,
+            object => unreachable!("expected a function, found {:?}", object),
+        }
+    }
+
+    pub fn closure(&self, reference: ObjRef) -> &Closure {
+        match self.get(reference) {
+            Object::Closure(closure) => closure,
+            object => unreachable!("expected a closure, found {:?}", object),
+        }
+    }
+
+    pub fn upvalue(&self, reference: ObjRef) -> &Upvalue {
+        match self.get(reference) {
+            Object::Upvalue(upvalue) => upvalue,
+            object => unreachable!("expected an upvalue, found {:?}", object),
+        }
+    }
+
+    pub fn upvalue_mut(&mut self, reference: ObjRef) -> &mut Upvalue {
+        match self.get_mut(reference) {
+            Object::Upvalue(upvalue) => upvalue,
+            object => unreachable!("expected an upvalue, found {:?}", object),
+        }
+    }
+
+    pub fn class(&self, reference: ObjRef) -> &Class {
+        match self.get(reference) {
+            Object::Class(class) => class,
+            object => unreachable!("expected a class, found {:?}", object),
+        }
+    }
+
+    pub fn class_mut(&mut self, reference: ObjRef) -> &mut Class {
+        match self.get_mut(reference) {
+            Object::Class(class) => class,
+            object => unreachable!("expected a class, found {:?}", object),
+        }
+    }
+
+    pub fn instance_mut(&mut self, reference: ObjRef) -> &mut Instance {
+        match self.get_mut(reference) {
+            Object::Instance(instance) => instance,
+            object => unreachable!("expected an instance, found {:?}", object),
+        }
+    }
+
+    pub fn bound_method(&self, reference: ObjRef) -> &BoundMethod {
+        match self.get(reference) {
+            Object::BoundMethod(bound) => bound,
+            object => unreachable!("expected a bound method, found {:?}", object),
+        }
+    }
+}
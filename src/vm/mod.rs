@@ -0,0 +1,993 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::chunk::{self, Constant, OpCode};
+use crate::diagnostics::codes;
+use crate::domain::{token::Token, Symbol, TokenType};
+use crate::interpreter::RuntimeError;
+
+pub mod heap;
+pub use heap::{Heap, ObjRef};
+
+pub mod object;
+pub use object::{NativeFn, Object};
+
+pub mod value;
+pub use value::Value;
+
+use object::{BoundMethod, Class, Closure, Instance, Native, Upvalue};
+
+/*
+    The virtual machine runs the bytecode produced by the `Compiler`. It is a
+    stack machine: instructions pop their operands off the value stack and push
+    their result, and every call gets a frame whose slots are a window on that
+    stack, starting with the callee itself.
+
+    It behaves exactly like the tree-walking Interpreter, which stays the
+    reference: same output, and the same runtime errors on the same lines.
+
+    Reference - https://craftinginterpreters.com/a-virtual-machine.html
+*/
+
+const FRAMES_MAX: usize = 10_000;
+
+struct CallFrame {
+    closure: ObjRef,
+    function: Rc<chunk::Function>,
+    constants: Rc<[Value]>,
+    ip: usize,
+    // index of the frame's first stack slot
+    slots: usize,
+}
+
+pub struct Vm {
+    pub heap: Heap,
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<ObjRef, Value>,
+    // captured variables still on the stack, at most one per slot
+    open_upvalues: Vec<ObjRef>,
+    init_string: ObjRef,
+    output: Box<dyn Write>,
+    // collect before every allocation, to flush out objects that aren't rooted
+    pub gc_stress: bool,
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        Self::with_output(Box::new(io::stdout()))
+    }
+
+    // `print` writes to the given output instead of stdout
+    pub fn with_output(output: Box<dyn Write>) -> Self {
+        let mut heap = Heap::new();
+        let init_string = heap.intern("init");
+        let mut vm = Self {
+            heap,
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals: HashMap::new(),
+            open_upvalues: Vec::new(),
+            init_string,
+            output,
+            gc_stress: false,
+        };
+
+        vm.define_native("clock", 0, |_, _| {
+            let elapsed = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|error| error.to_string())?;
+            Ok(Value::Number(elapsed.as_secs_f64()))
+        });
+
+        vm
+    }
+
+    pub fn define_native(&mut self, name: &str, arity: usize, function: NativeFn) {
+        // the name stays on the stack so allocating the native can't free it
+        let name_string = self.intern(name);
+        self.push(Value::Object(name_string));
+        let native = self.alloc(Object::Native(Native {
+            name: name.to_string(),
+            arity,
+            function,
+        }));
+        self.pop();
+        self.globals.insert(name_string, Value::Object(native));
+    }
+
+    pub fn interpret(&mut self, script: &Rc<chunk::Function>) -> Result<(), RuntimeError> {
+        let function = self.load(script);
+        self.push(Value::Object(function));
+        let closure = self.alloc(Object::Closure(Closure {
+            function,
+            upvalues: Vec::new(),
+        }));
+        self.pop();
+        self.push(Value::Object(closure));
+
+        let result = self.call(closure, 0).and_then(|()| self.run());
+        if result.is_err() {
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+        }
+        result
+    }
+
+    // moves a compiled function, and the ones nested in it, onto the heap
+    fn load(&mut self, function: &Rc<chunk::Function>) -> ObjRef {
+        // constants wait on the stack until the function holding them exists
+        let base = self.stack.len();
+        for constant in &function.chunk.constants {
+            let value = match constant {
+                Constant::Number(number) => Value::Number(*number),
+                Constant::String(string) => Value::Object(self.intern(string)),
+                Constant::Function(function) => Value::Object(self.load(function)),
+            };
+            self.push(value);
+        }
+
+        let constants: Rc<[Value]> = self.stack[base..].into();
+        let function = self.alloc(Object::Function(object::Function {
+            function: Rc::clone(function),
+            constants,
+        }));
+        self.stack.truncate(base);
+        function
+    }
+
+    // allocation is where collections happen, so everything the caller still
+    // needs must be reachable from a root by then
+    fn alloc(&mut self, object: Object) -> ObjRef {
+        if self.gc_stress || self.heap.should_collect() {
+            self.collect_garbage();
+        }
+        self.heap.alloc(object)
+    }
+
+    fn intern(&mut self, string: &str) -> ObjRef {
+        if self.gc_stress || self.heap.should_collect() {
+            self.collect_garbage();
+        }
+        self.heap.intern(string)
+    }
+
+    // frees every object the program can no longer reach
+    pub fn collect_garbage(&mut self) {
+        for value in &self.stack {
+            self.heap.mark_value(*value);
+        }
+        for (name, value) in &self.globals {
+            self.heap.mark_object(*name);
+            self.heap.mark_value(*value);
+        }
+        for upvalue in &self.open_upvalues {
+            self.heap.mark_object(*upvalue);
+        }
+        for frame in &self.frames {
+            self.heap.mark_object(frame.closure);
+        }
+        self.heap.mark_object(self.init_string);
+
+        self.heap.trace();
+        self.heap.sweep();
+    }
+
+    fn run(&mut self) -> Result<(), RuntimeError> {
+        loop {
+            let byte = self.read_byte();
+            let Ok(op) = OpCode::try_from(byte) else {
+                return Err(RuntimeError::uncoded(
+                    format!("Unknown opcode {}.", byte),
+                    self.error_token(),
+                ));
+            };
+
+            match op {
+                OpCode::Constant => {
+                    let constant = self.read_constant();
+                    self.push(constant);
+                }
+                OpCode::Nil => self.push(Value::Nil),
+                OpCode::True => self.push(Value::Boolean(true)),
+                OpCode::False => self.push(Value::Boolean(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::GetLocal => {
+                    let slot = self.frame().slots + self.read_byte() as usize;
+                    self.push(self.stack[slot]);
+                }
+                OpCode::SetLocal => {
+                    let slot = self.frame().slots + self.read_byte() as usize;
+                    self.stack[slot] = self.peek(0);
+                }
+                OpCode::GetGlobal => {
+                    let name = self.read_string();
+                    match self.globals.get(&name) {
+                        Some(value) => self.push(*value),
+                        None => return Err(self.undefined_variable(name)),
+                    }
+                }
+                OpCode::DefineGlobal => {
+                    let name = self.read_string();
+                    let value = self.pop();
+                    self.globals.insert(name, value);
+                }
+                OpCode::SetGlobal => {
+                    let name = self.read_string();
+                    if !self.globals.contains_key(&name) {
+                        return Err(self.undefined_variable(name));
+                    }
+                    self.globals.insert(name, self.peek(0));
+                }
+                OpCode::GetUpvalue => {
+                    let upvalue = self.frame_upvalue();
+                    let value = match self.heap.upvalue(upvalue) {
+                        Upvalue::Open(slot) => match self.stack.get(*slot) {
+                            Some(value) => *value,
+                            None => return Err(self.invalid_bytecode()),
+                        },
+                        Upvalue::Closed(value) => *value,
+                    };
+                    self.push(value);
+                }
+                OpCode::SetUpvalue => {
+                    let upvalue = self.frame_upvalue();
+                    let value = self.peek(0);
+                    match self.heap.upvalue_mut(upvalue) {
+                        Upvalue::Open(slot) => {
+                            let slot = *slot;
+                            match self.stack.get_mut(slot) {
+                                Some(variable) => *variable = value,
+                                None => return Err(self.invalid_bytecode()),
+                            }
+                        }
+                        Upvalue::Closed(closed) => *closed = value,
+                    }
+                }
+                OpCode::GetProperty => {
+                    let name = self.read_string();
+                    let Some(instance) = self.as_instance(self.peek(0)) else {
+                        return Err(self.error(
+                            codes::NOT_AN_INSTANCE,
+                            "Only instances have properties.".to_string(),
+                        ));
+                    };
+
+                    let (class, field) = match self.heap.get(instance) {
+                        Object::Instance(instance) => {
+                            (instance.class, instance.fields.get(&name).copied())
+                        }
+                        _ => unreachable!("checked to be an instance"),
+                    };
+                    match field {
+                        Some(value) => {
+                            self.pop();
+                            self.push(value);
+                        }
+                        None => self.bind_method(class, name)?,
+                    }
+                }
+                OpCode::SetProperty => {
+                    let name = self.read_string();
+                    let Some(instance) = self.as_instance(self.peek(1)) else {
+                        return Err(self.error(
+                            codes::NOT_AN_INSTANCE,
+                            "Only instances have fields.".to_string(),
+                        ));
+                    };
+
+                    let value = self.pop();
+                    self.heap.instance_mut(instance).fields.insert(name, value);
+                    self.pop();
+                    self.push(value);
+                }
+                OpCode::GetSuper => {
+                    let name = self.read_string();
+                    let superclass = self.pop();
+                    let Some(superclass) = self.as_class(superclass) else {
+                        return Err(self.invalid_bytecode());
+                    };
+                    self.bind_method(superclass, name)?;
+                }
+                OpCode::Equal => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.push(Value::Boolean(left == right));
+                }
+                OpCode::Greater => self.comparison(|left, right| left > right)?,
+                OpCode::GreaterEqual => self.comparison(|left, right| left >= right)?,
+                OpCode::Less => self.comparison(|left, right| left < right)?,
+                OpCode::LessEqual => self.comparison(|left, right| left <= right)?,
+                OpCode::Add => self.add()?,
+                OpCode::Subtract => self.arithmetic(|left, right| left - right)?,
+                OpCode::Multiply => self.arithmetic(|left, right| left * right)?,
+                OpCode::Divide => self.arithmetic(|left, right| left / right)?,
+                OpCode::Stringify => {
+                    let value = self.peek(0);
+                    if !self.is_string(value) {
+                        let string = self.format(value);
+                        let string = self.intern(&string);
+                        self.pop();
+                        self.push(Value::Object(string));
+                    }
+                }
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.push(Value::Boolean(!value.is_truthy()));
+                }
+                OpCode::Negate => {
+                    let Value::Number(number) = self.peek(0) else {
+                        return Err(self.error(
+                            codes::OPERAND_NOT_A_NUMBER,
+                            "Operand must be a number.".to_string(),
+                        ));
+                    };
+                    self.pop();
+                    self.push(Value::Number(-number));
+                }
+                OpCode::Print => {
+                    let value = self.pop();
+                    let text = self.format(value);
+                    // a closed stdout is not something a Lox program can recover from
+                    writeln!(self.output, "{}", text).expect("Failed to write output");
+                }
+                OpCode::Jump => {
+                    let offset = self.read_u16() as usize;
+                    self.frame_mut().ip += offset;
+                }
+                OpCode::JumpIfFalse => {
+                    let offset = self.read_u16() as usize;
+                    if !self.peek(0).is_truthy() {
+                        self.frame_mut().ip += offset;
+                    }
+                }
+                OpCode::Loop => {
+                    let offset = self.read_u16() as usize;
+                    self.frame_mut().ip -= offset;
+                }
+                OpCode::Call => {
+                    let argument_count = self.read_byte() as usize;
+                    self.call_value(self.peek(argument_count), argument_count)?;
+                }
+                OpCode::Closure => {
+                    let Value::Object(function) = self.read_constant() else {
+                        unreachable!("closures are always made from function constants");
+                    };
+
+                    let upvalue_count = self.heap.function(function).function.upvalue_count;
+                    let mut upvalues = Vec::with_capacity(upvalue_count);
+                    for _ in 0..upvalue_count {
+                        let is_local = self.read_byte() == 1;
+                        let index = self.read_byte() as usize;
+                        let upvalue = if is_local {
+                            self.capture_upvalue(self.frame().slots + index)
+                        } else {
+                            self.heap.closure(self.frame().closure).upvalues[index]
+                        };
+                        upvalues.push(upvalue);
+                    }
+
+                    let closure = self.alloc(Object::Closure(Closure { function, upvalues }));
+                    self.push(Value::Object(closure));
+                }
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                OpCode::Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().expect("returning from a call");
+                    self.close_upvalues(frame.slots);
+                    self.stack.truncate(frame.slots);
+
+                    if self.frames.is_empty() {
+                        return Ok(());
+                    }
+                    self.push(result);
+                }
+                OpCode::Class => {
+                    let name = self.read_string();
+                    let class = self.alloc(Object::Class(Class {
+                        name,
+                        methods: HashMap::new(),
+                    }));
+                    self.push(Value::Object(class));
+                }
+                OpCode::Inherit => {
+                    let Some(superclass) = self.as_class(self.peek(1)) else {
+                        return Err(self.error(
+                            codes::SUPERCLASS_NOT_A_CLASS,
+                            "Superclass must be a class.".to_string(),
+                        ));
+                    };
+                    let Some(subclass) = self.as_class(self.peek(0)) else {
+                        return Err(self.invalid_bytecode());
+                    };
+
+                    let methods = self.heap.class(superclass).methods.clone();
+                    self.heap.class_mut(subclass).methods.extend(methods);
+                    self.pop();
+                }
+                OpCode::Method => {
+                    let name = self.read_string();
+                    let (Some(method), Some(class)) =
+                        (self.as_closure(self.peek(0)), self.as_class(self.peek(1)))
+                    else {
+                        return Err(self.invalid_bytecode());
+                    };
+                    self.heap.class_mut(class).methods.insert(name, method);
+                    self.pop();
+                }
+            }
+        }
+    }
+
+    // helpers for calls
+
+    fn call_value(&mut self, callee: Value, argument_count: usize) -> Result<(), RuntimeError> {
+        if let Value::Object(reference) = callee {
+            let base = self.stack.len() - argument_count - 1;
+            match self.heap.get(reference) {
+                Object::Closure(_) => return self.call(reference, argument_count),
+                Object::Native(native) => {
+                    let (arity, function) = (native.arity, native.function);
+                    self.check_arity(arity, argument_count)?;
+
+                    let arguments = self.stack[base + 1..].to_vec();
+                    let result = function(&mut self.heap, &arguments)
+                        .map_err(|message| RuntimeError::uncoded(message, self.error_token()))?;
+                    self.stack.truncate(base);
+                    self.push(result);
+                    return Ok(());
+                }
+                Object::Class(class) => {
+                    let initializer = class.methods.get(&self.init_string).copied();
+                    let instance = self.alloc(Object::Instance(Instance {
+                        class: reference,
+                        fields: HashMap::new(),
+                    }));
+                    self.stack[base] = Value::Object(instance);
+
+                    return match initializer {
+                        Some(initializer) => self.call(initializer, argument_count),
+                        None => self.check_arity(0, argument_count),
+                    };
+                }
+                Object::BoundMethod(bound) => {
+                    let (receiver, method) = (bound.receiver, bound.method);
+                    self.stack[base] = receiver;
+                    return self.call(method, argument_count);
+                }
+                _ => {}
+            }
+        }
+
+        Err(self.error(
+            codes::NOT_CALLABLE,
+            "Can only call functions and classes.".to_string(),
+        ))
+    }
+
+    fn call(&mut self, closure: ObjRef, argument_count: usize) -> Result<(), RuntimeError> {
+        let function = self.heap.function(self.heap.closure(closure).function);
+        let (function, constants) = (
+            Rc::clone(&function.function),
+            Rc::clone(&function.constants),
+        );
+        self.check_arity(function.arity, argument_count)?;
+
+        if self.frames.len() == FRAMES_MAX {
+            return Err(self.error(codes::STACK_OVERFLOW, "Stack overflow.".to_string()));
+        }
+
+        self.frames.push(CallFrame {
+            closure,
+            function,
+            constants,
+            ip: 0,
+            slots: self.stack.len() - argument_count - 1,
+        });
+        Ok(())
+    }
+
+    fn check_arity(&self, arity: usize, argument_count: usize) -> Result<(), RuntimeError> {
+        if arity == argument_count {
+            return Ok(());
+        }
+        Err(self.error(
+            codes::WRONG_ARGUMENT_COUNT,
+            format!("Expected {} arguments but got {}.", arity, argument_count),
+        ))
+    }
+
+    // replaces the instance on top of the stack with its method bound to it
+    fn bind_method(&mut self, class: ObjRef, name: ObjRef) -> Result<(), RuntimeError> {
+        let Some(method) = self.heap.class(class).methods.get(&name).copied() else {
+            let message = format!("Undefined property '{}'.", self.heap.string(name));
+            return Err(self.error(codes::UNDEFINED_PROPERTY, message));
+        };
+
+        // the receiver stays on the stack while the bound method is allocated
+        let receiver = self.peek(0);
+        let bound = self.alloc(Object::BoundMethod(BoundMethod { receiver, method }));
+        self.pop();
+        self.push(Value::Object(bound));
+        Ok(())
+    }
+
+    // helpers for upvalues
+
+    fn frame_upvalue(&mut self) -> ObjRef {
+        let index = self.read_byte() as usize;
+        self.heap.closure(self.frame().closure).upvalues[index]
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> ObjRef {
+        let existing = self.open_upvalues.iter().copied().find(
+            |upvalue| matches!(self.heap.upvalue(*upvalue), Upvalue::Open(open) if *open == slot),
+        );
+        if let Some(upvalue) = existing {
+            return upvalue;
+        }
+
+        let upvalue = self.alloc(Object::Upvalue(Upvalue::Open(slot)));
+        self.open_upvalues.push(upvalue);
+        upvalue
+    }
+
+    // moves every captured variable at or above `last` off the stack
+    fn close_upvalues(&mut self, last: usize) {
+        let stack = &self.stack;
+        let heap = &mut self.heap;
+        self.open_upvalues.retain(|upvalue| {
+            let upvalue = heap.upvalue_mut(*upvalue);
+            match upvalue {
+                Upvalue::Open(slot) if *slot >= last => {
+                    // only a damaged file pops a captured variable without closing it
+                    let value = stack.get(*slot).copied().unwrap_or(Value::Nil);
+                    *upvalue = Upvalue::Closed(value);
+                    false
+                }
+                _ => true,
+            }
+        });
+    }
+
+    // helpers for operators, errors point at the operator
+
+    fn add(&mut self) -> Result<(), RuntimeError> {
+        match (self.peek(1), self.peek(0)) {
+            (Value::Number(left), Value::Number(right)) => {
+                self.pop();
+                self.pop();
+                self.push(Value::Number(left + right));
+                Ok(())
+            }
+            (Value::Object(left), Value::Object(right))
+                if matches!(self.heap.get(left), Object::String(_))
+                    && matches!(self.heap.get(right), Object::String(_)) =>
+            {
+                let concatenated = format!("{}{}", self.heap.string(left), self.heap.string(right));
+                let string = self.intern(&concatenated);
+                self.pop();
+                self.pop();
+                self.push(Value::Object(string));
+                Ok(())
+            }
+            _ => Err(self.error(
+                codes::INVALID_PLUS_OPERANDS,
+                "Operands must be two numbers or two strings.".to_string(),
+            )),
+        }
+    }
+
+    fn number_operands(&mut self) -> Result<(f64, f64), RuntimeError> {
+        let (Value::Number(left), Value::Number(right)) = (self.peek(1), self.peek(0)) else {
+            return Err(self.error(
+                codes::OPERAND_NOT_A_NUMBER,
+                "Operand must be a number.".to_string(),
+            ));
+        };
+        self.pop();
+        self.pop();
+        Ok((left, right))
+    }
+
+    fn arithmetic(&mut self, operation: fn(f64, f64) -> f64) -> Result<(), RuntimeError> {
+        let (left, right) = self.number_operands()?;
+        self.push(Value::Number(operation(left, right)));
+        Ok(())
+    }
+
+    fn comparison(&mut self, operation: fn(f64, f64) -> bool) -> Result<(), RuntimeError> {
+        let (left, right) = self.number_operands()?;
+        self.push(Value::Boolean(operation(left, right)));
+        Ok(())
+    }
+
+    // helpers to read the current instruction
+
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().expect("running inside a call")
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().expect("running inside a call")
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let frame = self.frame_mut();
+        let byte = frame.function.chunk.code[frame.ip];
+        frame.ip += 1;
+        byte
+    }
+
+    fn read_u16(&mut self) -> u16 {
+        let frame = self.frame_mut();
+        let value = frame.function.chunk.read_u16(frame.ip);
+        frame.ip += 2;
+        value
+    }
+
+    fn read_constant(&mut self) -> Value {
+        let index = self.read_u16() as usize;
+        self.frame().constants[index]
+    }
+
+    fn read_string(&mut self) -> ObjRef {
+        match self.read_constant() {
+            Value::Object(string) => string,
+            value => unreachable!("expected a name constant, found {:?}", value),
+        }
+    }
+
+    // helpers for the stack
+
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack
+            .pop()
+            .expect("the compiler keeps the stack balanced")
+    }
+
+    fn peek(&self, distance: usize) -> Value {
+        self.stack[self.stack.len() - 1 - distance]
+    }
+
+    fn as_instance(&self, value: Value) -> Option<ObjRef> {
+        match value {
+            Value::Object(reference) => {
+                matches!(self.heap.get(reference), Object::Instance(_)).then_some(reference)
+            }
+            _ => None,
+        }
+    }
+
+    fn is_string(&self, value: Value) -> bool {
+        matches!(value, Value::Object(reference) if matches!(self.heap.get(reference), Object::String(_)))
+    }
+
+    fn as_closure(&self, value: Value) -> Option<ObjRef> {
+        match value {
+            Value::Object(reference) => {
+                matches!(self.heap.get(reference), Object::Closure(_)).then_some(reference)
+            }
+            _ => None,
+        }
+    }
+
+    fn as_class(&self, value: Value) -> Option<ObjRef> {
+        match value {
+            Value::Object(reference) => {
+                matches!(self.heap.get(reference), Object::Class(_)).then_some(reference)
+            }
+            _ => None,
+        }
+    }
+
+    // how `print` shows a value, the same as the tree-walking Interpreter
+    pub fn format(&self, value: Value) -> String {
+        match value {
+            Value::Nil => "nil".to_string(),
+            Value::Boolean(boolean) => boolean.to_string(),
+            Value::Number(number) => format!("{:?}", number),
+            Value::Object(reference) => match self.heap.get(reference) {
+                Object::String(string) => string.clone(),
+                Object::Function(function) => function.function.to_string(),
+                Object::Native(_) => "<native fn>".to_string(),
+                Object::Closure(closure) => self.format(Value::Object(closure.function)),
+                Object::Upvalue(_) => "upvalue".to_string(),
+                Object::Class(class) => self.heap.string(class.name).to_string(),
+                Object::Instance(instance) => {
+                    let class = self.heap.class(instance.class);
+                    format!("{} instance", self.heap.string(class.name))
+                }
+                Object::BoundMethod(bound) => self.format(Value::Object(bound.method)),
+            },
+        }
+    }
+
+    fn error(&self, code: &'static str, message: String) -> RuntimeError {
+        RuntimeError::new(code, message, self.error_token())
+    }
+
+    // loaded files are verified, this catches what only running can tell
+    fn invalid_bytecode(&self) -> RuntimeError {
+        RuntimeError::uncoded("Invalid bytecode.".to_string(), self.error_token())
+    }
+
+    // errors are reported at the instruction being executed in the innermost frame
+    fn error_token(&self) -> Token {
+        let frame = self.frame();
+        let offset = frame.ip.saturating_sub(1);
+        let chunk = &frame.function.chunk;
+
+        // the instruction's line and span stand in for the token the
+        // tree-walker would report
+        Token::new(
+            TokenType::Eof,
+            Symbol::intern(""),
+            None,
+            chunk.line_at(offset),
+            chunk.line_at(offset),
+            0,
+            chunk.span_at(offset),
+        )
+    }
+
+    fn undefined_variable(&self, name: ObjRef) -> RuntimeError {
+        self.error(
+            codes::UNDEFINED_VARIABLE,
+            format!("Undefined variable '{}'.", self.heap.string(name)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use crate::interpreter::Interpreter;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+    use std::cell::RefCell;
+
+    // collects everything the VM prints so tests can inspect it
+    #[derive(Clone, Default)]
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedOutput {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.borrow().clone()).unwrap()
+        }
+    }
+
+    // runs the source on both backends, returning the output and error of each
+    fn run_both(source: &str) -> [(String, Option<String>); 2] {
+        let statements = Parser::new(Scanner::new(source)).parse();
+        let mut resolver = Resolver::new();
+        resolver.resolve(&statements);
+        assert!(resolver.errors.is_empty(), "unexpected resolver errors");
+
+        let output = SharedOutput::default();
+        let mut interpreter = Interpreter::with_output(Box::new(output.clone()));
+        interpreter.resolve(resolver.locals);
+        let error = interpreter.interpret(&statements).err();
+        let tree_walker = (output.text(), error.map(|error| error.to_string()));
+
+        let mut compiler = Compiler::new(source);
+        let script = compiler.compile(&statements);
+        assert!(compiler.errors.is_empty(), "unexpected compile errors");
+
+        let run_vm = |gc_stress| {
+            let output = SharedOutput::default();
+            let mut vm = Vm::with_output(Box::new(output.clone()));
+            vm.gc_stress = gc_stress;
+            let error = vm.interpret(&script).err();
+            (output.text(), error.map(|error| error.to_string()))
+        };
+        let vm = run_vm(false);
+        assert_eq!(
+            vm,
+            run_vm(true),
+            "collecting changed the result of:\n{}",
+            source
+        );
+
+        [tree_walker, vm]
+    }
+
+    fn assert_same(source: &str) {
+        let [tree_walker, vm] = run_both(source);
+        assert_eq!(tree_walker, vm, "backends disagree on:\n{}", source);
+    }
+
+    #[test]
+    fn test_vm_expressions_and_globals() {
+        assert_same(
+            "var a = 1; var b; print a + 2; print b; var a = \"x\"; print a + \"y\";
+             print 10 / 4 - -1; print 1 / 0; print !nil; print 1 == 1.0; print \"a\" != \"a\";
+             print 0 / 0 >= 0; print nil or \"default\"; print 0 and \"second\";",
+        );
+    }
+
+    #[test]
+    fn test_vm_string_interpolation() {
+        assert_same(
+            "var n = 3; class Box {} fun f() {}
+             print \"n=${n}, half=${n / 2}, ${nil} ${true} ${Box} ${Box()} ${f} ${clock}\";
+             print \"nested ${\"<${n + 1}>\"} and ${\"\"}${\"x\"}\\t!\";
+             var s = \"${n}\"; print s + \"!\"; print \"${n}\" + 1;",
+        );
+    }
+
+    #[test]
+    fn test_vm_control_flow_and_scopes() {
+        assert_same(
+            "var a = \"global\";
+             { var a = \"inner\"; print a; { var b = a; a = \"both\"; print b; } print a; }
+             print a;
+             if (a == \"global\") print 1; else print 2;
+             var i = 0;
+             while (i < 3) { print i; i = i + 1; }
+             for (var j = 0; j < 2; j = j + 1) { var k = j * 10; print k; }",
+        );
+    }
+
+    #[test]
+    fn test_vm_functions_and_closures() {
+        assert_same(
+            "fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+             print fib(15);
+             fun makeCounter() {
+                 var i = 0;
+                 fun count() { i = i + 1; return i; }
+                 return count;
+             }
+             var counter = makeCounter();
+             counter();
+             print counter();
+             print counter;
+             print clock;
+             print clock() > 0;
+             var closures;
+             for (var i = 0; i < 3; i = i + 1) {
+                 fun show() { print i; }
+                 if (i == 1) closures = show;
+             }
+             closures();
+             fun outer() {
+                 var x = \"outer\";
+                 fun middle() { fun inner() { return x; } return inner; }
+                 return middle()();
+             }
+             print outer();",
+        );
+    }
+
+    #[test]
+    fn test_vm_classes_and_inheritance() {
+        assert_same(
+            "class A {
+                 init(name) { this.name = name; }
+                 greet() { return \"A \" + this.name; }
+             }
+             class B < A {
+                 greet() { return \"B then \" + super.greet(); }
+             }
+             var b = B(\"bob\");
+             print b.greet();
+             print B(\"temporary\").greet();
+             print b;
+             print B;
+             print b.init(\"again\") == b;
+             print b.name;
+             var greet = b.greet;
+             b.name = \"carol\";
+             print greet();
+             print greet;
+             class Box {}
+             var box = Box();
+             box.size = 3;
+             print box.size;
+             fun helper() { return box; }
+             helper().size = helper().size + 1;
+             print box.size;",
+        );
+    }
+
+    #[test]
+    fn test_vm_runtime_errors_match() {
+        for source in [
+            "print -\"a\";",
+            "print 1;\nprint 1 < \"x\";",
+            "print \"a\" + 1;",
+            "print missing;",
+            "missing = 1;",
+            "fun f(a, b) {}\nf(1\n);",
+            "\"not callable\"();",
+            "class A {}\nA().missing;",
+            "var a = 1;\na.field = 2;",
+            "var a = 1;\nprint a.field;",
+            "class A {}\nA(1);",
+            "var NotAClass = 1;\nclass B < NotAClass {}",
+            "class A {}\nclass B < A { m() { return super.missing; } }\nB().m();",
+        ] {
+            assert_same(source);
+        }
+    }
+
+    #[test]
+    fn test_vm_stack_overflow() {
+        let statements = Parser::new(Scanner::new("fun f() { f(); } f();")).parse();
+        let script = Compiler::new("").compile(&statements);
+
+        let error = Vm::with_output(Box::new(io::sink()))
+            .interpret(&script)
+            .unwrap_err();
+        assert_eq!(error.message, "Stack overflow.");
+    }
+
+    #[test]
+    fn test_vm_stack_overflow_matches_tree_walker() {
+        // the tree-walker's default depth assumes a main thread's stack
+        let main_sized = std::thread::Builder::new().stack_size(8 << 20);
+        main_sized
+            .spawn(|| {
+                assert_same("var depth = 0;\nfun f() {\n  depth = depth + 1;\n  f();\n}\nf();")
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_vm_collects_cycles() {
+        // each instance holds a closure that captures the instance
+        let source = "class Node {
+                          init() { var node = this; fun get() { return node; } this.get = get; }
+                      }
+                      for (var i = 0; i < 500; i = i + 1) { Node().get().get(); }
+                      var kept = Node();";
+        let statements = Parser::new(Scanner::new(source)).parse();
+        let script = Compiler::new(source).compile(&statements);
+
+        let mut vm = Vm::with_output(Box::new(io::sink()));
+        vm.interpret(&script).unwrap();
+        let before = vm.heap.len();
+        vm.collect_garbage();
+
+        assert!(before > 1000, "only {} objects were allocated", before);
+        assert!(vm.heap.len() < 50, "{} objects survived", vm.heap.len());
+
+        // the surviving instance is still intact
+        let kept = vm.heap.intern("kept");
+        let value = vm.globals[&kept];
+        assert_eq!(vm.format(value), "Node instance");
+    }
+}
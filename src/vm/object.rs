@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::heap::{Heap, ObjRef};
+use super::Value;
+use crate::chunk;
+
+/*
+    The objects the virtual machine allocates on its `Heap`. Objects refer to
+    each other through `ObjRef` handles, never directly.
+*/
+
+pub type NativeFn = fn(&mut Heap, &[Value]) -> Result<Value, String>;
+
+#[derive(Debug)]
+pub enum Object {
+    String(String),
+    Function(Function),
+    Native(Native),
+    Closure(Closure),
+    Upvalue(Upvalue),
+    Class(Class),
+    Instance(Instance),
+    BoundMethod(BoundMethod),
+}
+
+impl Object {
+    // roughly how many bytes the object holds on to, to pace the collector
+    pub fn size(&self) -> usize {
+        let owned = match self {
+            Object::String(string) => string.len(),
+            Object::Function(function) => function.constants.len() * size_of::<Value>(),
+            Object::Native(native) => native.name.len(),
+            Object::Closure(closure) => closure.upvalues.len() * size_of::<ObjRef>(),
+            Object::Upvalue(_) | Object::BoundMethod(_) => 0,
+            Object::Class(class) => class.methods.len() * 2 * size_of::<ObjRef>(),
+            Object::Instance(instance) => {
+                instance.fields.len() * (size_of::<ObjRef>() + size_of::<Value>())
+            }
+        };
+        size_of::<Object>() + owned
+    }
+}
+
+// a compiled function with its constants turned into values
+#[derive(Debug)]
+pub struct Function {
+    pub function: Rc<chunk::Function>,
+    pub constants: Rc<[Value]>,
+}
+
+pub struct Native {
+    pub name: String,
+    pub arity: usize,
+    pub function: NativeFn,
+}
+
+impl std::fmt::Debug for Native {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Native")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+pub struct Closure {
+    pub function: ObjRef,
+    pub upvalues: Vec<ObjRef>,
+}
+
+// a captured variable, on the stack until its scope ends and then moved here
+#[derive(Debug)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+#[derive(Debug)]
+pub struct Class {
+    pub name: ObjRef,
+    // method name -> closure
+    pub methods: HashMap<ObjRef, ObjRef>,
+}
+
+#[derive(Debug)]
+pub struct Instance {
+    pub class: ObjRef,
+    pub fields: HashMap<ObjRef, Value>,
+}
+
+#[derive(Debug)]
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: ObjRef,
+}
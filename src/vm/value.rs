@@ -0,0 +1,23 @@
+use super::heap::ObjRef;
+
+/*
+    A Value is what lives on the virtual machine's stack. Anything bigger than
+    a number is an object on the `Heap`, and the value is a handle to it.
+
+    Strings are interned, so two equal strings are always the same object and
+    comparing handles is enough for equality.
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Nil,
+    Boolean(bool),
+    Number(f64),
+    Object(ObjRef),
+}
+
+impl Value {
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Boolean(false))
+    }
+}